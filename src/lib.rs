@@ -1,339 +1,4301 @@
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Deref, Range};
 use serde::{Deserialize, Serialize};
-use std::fmt;
-use std::ops::Deref;
+use sha2::{Digest, Sha256};
+#[cfg(feature = "std")]
+use std::io;
+
+// Minimal stand-in for `std::io::Write` so encoding works without `std`
+#[cfg(not(feature = "std"))]
+pub mod io {
+    use alloc::vec::Vec;
+
+    #[derive(Debug)]
+    pub struct Error;
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    pub trait Write {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+    }
+
+    impl Write for Vec<u8> {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            self.extend_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            (**self).write_all(buf)
+        }
+    }
+}
+
+// Stand-in for the parts of the `hex` crate used here, for builds without it
+#[cfg(not(feature = "hex"))]
+mod hex {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    #[derive(Debug)]
+    pub struct FromHexError;
+
+    impl fmt::Display for FromHexError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "invalid hex string")
+        }
+    }
+
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    pub fn encode<T: AsRef<[u8]>>(data: T) -> String {
+        let data = data.as_ref();
+        let mut s = String::with_capacity(data.len() * 2);
+        for &byte in data {
+            s.push(DIGITS[(byte >> 4) as usize] as char);
+            s.push(DIGITS[(byte & 0x0f) as usize] as char);
+        }
+        s
+    }
+
+    fn nibble(c: u8) -> Result<u8, FromHexError> {
+        match c {
+            b'0'..=b'9' => Ok(c - b'0'),
+            b'a'..=b'f' => Ok(c - b'a' + 10),
+            b'A'..=b'F' => Ok(c - b'A' + 10),
+            _ => Err(FromHexError),
+        }
+    }
+
+    pub fn decode<T: AsRef<[u8]>>(data: T) -> Result<Vec<u8>, FromHexError> {
+        let data = data.as_ref();
+        if data.len() % 2 != 0 {
+            return Err(FromHexError);
+        }
+        data.chunks(2)
+            .map(|pair| Ok((nibble(pair[0])? << 4) | nibble(pair[1])?))
+            .collect()
+    }
+}
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct CompactSize {
     pub value: u64,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorKind {
+    InsufficientBytes,
+    InvalidFormat,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum BitcoinError {
     InsufficientBytes,
     InvalidFormat,
+    // Failure while parsing a transaction, with the byte offset of the field that broke
+    ParseError { offset: usize, kind: ErrorKind },
+    InputIndexOutOfRange { index: usize, count: usize },
+    AmountCountMismatch { expected: usize, actual: usize },
+    ScriptTypeCountMismatch { expected: usize, actual: usize },
+    NegativeFee,
+    ValueOverflow,
+    NonStandardVersion(u32),
+    // Reading input failed; holds the underlying error message
+    Io(String),
+    AmountOutOfRange,
+    // `from_bytes` was handed a zero-length buffer
+    EmptyInput,
+    // Structural rule failures reported by `check_sanity`
+    NoInputs,
+    NoOutputs,
+    DuplicateInput { index: usize },
+    NullPrevout { index: usize },
+    CoinbaseScriptLength(usize),
+}
+
+impl BitcoinError {
+    /// Attaches a byte offset to a bare parse error
+    fn at(self, offset: usize) -> Self {
+        match self {
+            BitcoinError::InsufficientBytes => BitcoinError::ParseError {
+                offset,
+                kind: ErrorKind::InsufficientBytes,
+            },
+            BitcoinError::InvalidFormat => BitcoinError::ParseError {
+                offset,
+                kind: ErrorKind::InvalidFormat,
+            },
+            other => other,
+        }
+    }
+
+    /// Moves an error from a nested buffer to the offset `by` bytes in
+    fn shifted(self, by: usize) -> Self {
+        match self {
+            BitcoinError::ParseError { offset, kind } => BitcoinError::ParseError {
+                offset: offset + by,
+                kind,
+            },
+            other => other.at(by),
+        }
+    }
+
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            BitcoinError::ParseError { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::InsufficientBytes => write!(f, "insufficient bytes"),
+            ErrorKind::InvalidFormat => write!(f, "invalid format"),
+        }
+    }
+}
+
+impl fmt::Display for BitcoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitcoinError::InsufficientBytes => write!(f, "insufficient bytes"),
+            BitcoinError::InvalidFormat => write!(f, "invalid format"),
+            BitcoinError::ParseError { offset, kind } => {
+                write!(f, "{} at byte offset {}", kind, offset)
+            }
+            BitcoinError::InputIndexOutOfRange { index, count } => {
+                write!(f, "input index {} out of range for {} inputs", index, count)
+            }
+            BitcoinError::AmountCountMismatch { expected, actual } => {
+                write!(f, "expected {} input amounts, got {}", expected, actual)
+            }
+            BitcoinError::ScriptTypeCountMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "expected {} input script types, got {}",
+                    expected, actual
+                )
+            }
+            BitcoinError::NegativeFee => write!(f, "outputs exceed inputs"),
+            BitcoinError::ValueOverflow => write!(f, "value sum overflows u64"),
+            BitcoinError::NonStandardVersion(version) => {
+                write!(f, "non-standard transaction version {}", version)
+            }
+            BitcoinError::Io(message) => write!(f, "I/O error: {}", message),
+            BitcoinError::AmountOutOfRange => write!(f, "amount outside 0..=21,000,000 BTC"),
+            BitcoinError::EmptyInput => write!(f, "empty input"),
+            BitcoinError::NoInputs => write!(f, "transaction has no inputs"),
+            BitcoinError::NoOutputs => write!(f, "transaction has no outputs"),
+            BitcoinError::DuplicateInput { index } => {
+                write!(f, "input {} spends an outpoint already spent", index)
+            }
+            BitcoinError::NullPrevout { index } => {
+                write!(f, "non-coinbase input {} spends the null outpoint", index)
+            }
+            BitcoinError::CoinbaseScriptLength(len) => {
+                write!(f, "coinbase scriptSig is {} bytes, expected 2..=100", len)
+            }
+        }
+    }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for BitcoinError {}
+
 impl CompactSize {
     pub fn new(value: u64) -> Self {
         CompactSize { value }
     }
 
+    /// Widening `usize` to `u64` is lossless on every target Rust supports
+    pub fn from_usize(n: usize) -> Self {
+        CompactSize::new(n as u64)
+    }
+
+    /// The length prefix for `slice`
+    pub fn from_len<T>(slice: &[T]) -> Self {
+        CompactSize::from_usize(slice.len())
+    }
+
+    /// Like `new`, but rejects values above `max` with `InvalidFormat`
+    pub fn try_new(value: u64, max: u64) -> Result<Self, BitcoinError> {
+        if value > max {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(CompactSize { value })
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        self.consensus_encode(&mut bytes)
+            .expect("writing to a Vec never fails");
+        bytes
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.value
+    }
+
+    /// Number of bytes `to_bytes` produces: 1, 3, 5 or 9
+    pub fn encoded_len(&self) -> usize {
+        match self.value {
+            0..=0xFC => 1,
+            0xFD..=0xFFFF => 3,
+            0x10000..=0xFFFFFFFF => 5,
+            0x100000000..=u64::MAX => 9,
+        }
+    }
+
+    /// Total encoded length, prefix included, implied by the first byte
+    pub fn len_from_prefix(first_byte: u8) -> usize {
+        match first_byte {
+            0xFD => 3,
+            0xFE => 5,
+            0xFF => 9,
+            _ => 1,
+        }
+    }
+
+    pub fn to_bytes_hex(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    pub fn consensus_encode<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
         match self.value {
-            0..=0xFC => vec![self.value as u8],
+            0..=0xFC => {
+                w.write_all(&[self.value as u8])?;
+                Ok(1)
+            }
             0xFD..=0xFFFF => {
-                let mut bytes = vec![0xFD];
-                bytes.extend_from_slice(&(self.value as u16).to_le_bytes());
-                bytes
+                w.write_all(&[0xFD])?;
+                w.write_all(&(self.value as u16).to_le_bytes())?;
+                Ok(3)
             }
             0x10000..=0xFFFFFFFF => {
-                let mut bytes = vec![0xFE];
-                bytes.extend_from_slice(&(self.value as u32).to_le_bytes());
-                bytes
+                w.write_all(&[0xFE])?;
+                w.write_all(&(self.value as u32).to_le_bytes())?;
+                Ok(5)
             }
-            _ => {
-                let mut bytes = vec![0xFF];
-                bytes.extend_from_slice(&self.value.to_le_bytes());
-                bytes
+            0x100000000..=u64::MAX => {
+                w.write_all(&[0xFF])?;
+                w.write_all(&self.value.to_le_bytes())?;
+                Ok(9)
             }
         }
     }
 
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        if bytes.is_empty() {
-            return Err(BitcoinError::InsufficientBytes);
-        }
+        ensure_non_empty(bytes)?;
+        <Self as Decodable>::decode(bytes)
+    }
 
-        match bytes[0] {
-            0x00..=0xFC => Ok((CompactSize::new(bytes[0] as u64), 1)),
-            0xFD => {
-                if bytes.len() < 3 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
-                let value = u16::from_le_bytes([bytes[1], bytes[2]]) as u64;
-                Ok((CompactSize::new(value), 3))
-            }
-            0xFE => {
-                if bytes.len() < 5 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
-                let value = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]) as u64;
-                Ok((CompactSize::new(value), 5))
-            }
-            0xFF => {
-                if bytes.len() < 9 {
-                    return Err(BitcoinError::InsufficientBytes);
-                }
-                let value = u64::from_le_bytes([
-                    bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7], bytes[8],
-                ]);
-                Ok((CompactSize::new(value), 9))
-            }
-        }
+    /// Reads a CompactSize from a stream, consuming only the bytes it needs.
+    /// EOF surfaces as `UnexpectedEof`, non-canonical encodings as `InvalidData`.
+    #[cfg(feature = "std")]
+    pub fn from_reader<R: io::Read>(r: &mut R) -> io::Result<Self> {
+        let mut buf = [0u8; 9];
+        r.read_exact(&mut buf[..1])?;
+        let len = CompactSize::len_from_prefix(buf[0]);
+        r.read_exact(&mut buf[1..len])?;
+
+        let (compact, _) = CompactSize::from_bytes(&buf[..len])
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(compact)
+    }
+
+    /// Like `from_bytes`, but accepts non-canonical (non-minimal) encodings
+    pub fn from_bytes_unchecked(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        ensure_non_empty(bytes)?;
+        let mut reader = BitcoinReader::new(bytes);
+        let compact = reader.read_compact_size_unchecked()?;
+        Ok((compact, reader.position()))
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct Txid(pub [u8; 32]);
+/// Shows the value alongside its encoding, e.g. `253 (fdfd00)`
+impl fmt::Display for CompactSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.value, self.to_bytes_hex())
+    }
+}
 
-impl Serialize for Txid {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let hex_string = hex::encode(&self.0);
-        serializer.serialize_str(&hex_string)
+impl From<u64> for CompactSize {
+    fn from(value: u64) -> Self {
+        CompactSize::new(value)
     }
 }
 
-impl<'de> Deserialize<'de> for Txid {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let hex_string = String::deserialize(deserializer)?;
-        let bytes = hex::decode(&hex_string).map_err(serde::de::Error::custom)?;
-        if bytes.len() != 32 {
-            return Err(serde::de::Error::custom("Txid must be exactly 32 bytes"));
-        }
-        let mut array = [0u8; 32];
-        array.copy_from_slice(&bytes);
-        Ok(Txid(array))
+impl From<CompactSize> for u64 {
+    fn from(compact: CompactSize) -> Self {
+        compact.value
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
-pub struct OutPoint {
-    pub txid: Txid,
-    pub vout: u32,
+impl PartialEq<u64> for CompactSize {
+    fn eq(&self, other: &u64) -> bool {
+        self.value == *other
+    }
 }
 
-impl OutPoint {
-    pub fn new(txid: [u8; 32], vout: u32) -> Self {
-        OutPoint {
-            txid: Txid(txid),
-            vout,
-        }
+impl PartialOrd<u64> for CompactSize {
+    fn partial_cmp(&self, other: &u64) -> Option<core::cmp::Ordering> {
+        self.value.partial_cmp(other)
     }
+}
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.txid.0);
-        bytes.extend_from_slice(&self.vout.to_le_bytes());
-        bytes
+/// Types that can be decoded from the front of a byte slice, returning the
+/// value and the number of bytes consumed
+pub trait Decodable: Sized {
+    /// Lower bound on the encoded size, used to reject impossible counts
+    const MIN_ENCODED_LEN: usize = 1;
+
+    fn decode(bytes: &[u8]) -> Result<(Self, usize), BitcoinError>;
+
+    /// Decodes at the reader's position and advances past the value
+    fn read_from(reader: &mut BitcoinReader<'_>) -> Result<Self, BitcoinError> {
+        let (value, consumed) = Self::decode(reader.rest())?;
+        reader.position += consumed;
+        Ok(value)
     }
+}
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        if bytes.len() < 36 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
+/// Decodes a CompactSize count followed by that many items
+pub fn decode_vec<T: Decodable>(bytes: &[u8]) -> Result<(Vec<T>, usize), BitcoinError> {
+    let mut reader = BitcoinReader::new(bytes);
+    let items = reader.read_vec()?;
+    Ok((items, reader.position()))
+}
 
-        let mut txid = [0u8; 32];
-        txid.copy_from_slice(&bytes[0..32]);
-        let vout = u32::from_le_bytes([bytes[32], bytes[33], bytes[34], bytes[35]]);
+/// Types with a consensus byte serialization
+pub trait Encodable {
+    fn encode(&self) -> Vec<u8>;
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
 
-        Ok((OutPoint::new(txid, vout), 36))
+/// The wire-format contract: every multi-byte integer in the consensus
+/// encoding is little-endian. Hashes such as txids are written in internal
+/// byte order and only reversed for display (`txid_hex`).
+pub trait WireFormat: Encodable {
+    fn byte_order() -> ByteOrder {
+        ByteOrder::LittleEndian
     }
+
+    /// Length of `encode()`, computed without serializing
+    fn wire_size(&self) -> usize;
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
-pub struct Script {
-    pub bytes: Vec<u8>,
+impl Decodable for CompactSize {
+    fn decode(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut reader = BitcoinReader::new(bytes);
+        let compact = reader.read_compact_size()?;
+        Ok((compact, reader.position()))
+    }
+
+    fn read_from(reader: &mut BitcoinReader<'_>) -> Result<Self, BitcoinError> {
+        reader.read_compact_size()
+    }
 }
 
-impl Script {
-    pub fn new(bytes: Vec<u8>) -> Self {
-        Script { bytes }
+impl Encodable for CompactSize {
+    fn encode(&self) -> Vec<u8> {
+        self.to_bytes()
     }
+}
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut result = Vec::new();
-        let length = CompactSize::new(self.bytes.len() as u64);
-        result.extend_from_slice(&length.to_bytes());
-        result.extend_from_slice(&self.bytes);
-        result
+impl WireFormat for CompactSize {
+    fn wire_size(&self) -> usize {
+        self.encoded_len()
     }
+}
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let (length, consumed) = CompactSize::from_bytes(bytes)?;
-        let total_bytes_needed = consumed + length.value as usize;
+impl Decodable for OutPoint {
+    const MIN_ENCODED_LEN: usize = 36;
 
-        if bytes.len() < total_bytes_needed {
-            return Err(BitcoinError::InsufficientBytes);
-        }
+    fn decode(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut reader = BitcoinReader::new(bytes);
+        let outpoint = reader.read_outpoint()?;
+        Ok((outpoint, reader.position()))
+    }
 
-        let script_bytes = bytes[consumed..total_bytes_needed].to_vec();
-        Ok((Script::new(script_bytes), total_bytes_needed))
+    fn read_from(reader: &mut BitcoinReader<'_>) -> Result<Self, BitcoinError> {
+        reader.read_outpoint()
     }
 }
 
-impl Deref for Script {
-    type Target = Vec<u8>;
-    fn deref(&self) -> &Self::Target {
-        &self.bytes
+impl Encodable for OutPoint {
+    fn encode(&self) -> Vec<u8> {
+        self.to_bytes()
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
-pub struct TransactionInput {
-    pub previous_output: OutPoint,
-    pub script_sig: Script,
-    pub sequence: u32,
+impl WireFormat for OutPoint {
+    fn wire_size(&self) -> usize {
+        <Self as Decodable>::MIN_ENCODED_LEN
+    }
 }
 
-impl TransactionInput {
-    pub fn new(previous_output: OutPoint, script_sig: Script, sequence: u32) -> Self {
-        TransactionInput {
-            previous_output,
-            script_sig,
-            sequence,
-        }
+impl Decodable for Script {
+    fn decode(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut reader = BitcoinReader::new(bytes);
+        let script = reader.read_script()?;
+        Ok((script, reader.position()))
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.previous_output.to_bytes());
-        bytes.extend_from_slice(&self.script_sig.to_bytes());
-        bytes.extend_from_slice(&self.sequence.to_le_bytes());
-        bytes
+    fn read_from(reader: &mut BitcoinReader<'_>) -> Result<Self, BitcoinError> {
+        reader.read_script()
     }
+}
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let mut offset = 0;
+impl Encodable for Script {
+    fn encode(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+}
 
-        // Parse OutPoint (36 bytes)
-        let (previous_output, consumed) = OutPoint::from_bytes(&bytes[offset..])?;
-        offset += consumed;
+impl WireFormat for Script {
+    fn wire_size(&self) -> usize {
+        CompactSize::from_len(&self.bytes).encoded_len() + self.bytes.len()
+    }
+}
 
-        // Parse Script (with CompactSize)
-        let (script_sig, consumed) = Script::from_bytes(&bytes[offset..])?;
-        offset += consumed;
+impl Decodable for TransactionInput {
+    const MIN_ENCODED_LEN: usize = 41;
 
-        // Parse sequence (4 bytes)
-        if bytes.len() < offset + 4 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
-        let sequence = u32::from_le_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]);
-        offset += 4;
+    fn decode(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut reader = BitcoinReader::new(bytes);
+        let input = reader.read_input()?;
+        Ok((input, reader.position()))
+    }
 
-        Ok((
-            TransactionInput::new(previous_output, script_sig, sequence),
-            offset,
-        ))
+    fn read_from(reader: &mut BitcoinReader<'_>) -> Result<Self, BitcoinError> {
+        reader.read_input()
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
-pub struct BitcoinTransaction {
-    pub version: u32,
-    pub inputs: Vec<TransactionInput>,
-    pub lock_time: u32,
+impl Encodable for TransactionInput {
+    fn encode(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
 }
 
-impl BitcoinTransaction {
-    pub fn new(version: u32, inputs: Vec<TransactionInput>, lock_time: u32) -> Self {
-        BitcoinTransaction {
-            version,
-            inputs,
-            lock_time,
-        }
+impl WireFormat for TransactionInput {
+    fn wire_size(&self) -> usize {
+        36 + self.script_sig.wire_size() + 4
     }
+}
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+impl Decodable for TransactionOutput {
+    const MIN_ENCODED_LEN: usize = 9;
 
-        // Version (4 bytes LE)
-        bytes.extend_from_slice(&self.version.to_le_bytes());
+    fn decode(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut reader = BitcoinReader::new(bytes);
+        let output = reader.read_output()?;
+        Ok((output, reader.position()))
+    }
 
-        // CompactSize (number of inputs)
-        let input_count = CompactSize::new(self.inputs.len() as u64);
-        bytes.extend_from_slice(&input_count.to_bytes());
+    fn read_from(reader: &mut BitcoinReader<'_>) -> Result<Self, BitcoinError> {
+        reader.read_output()
+    }
+}
 
-        // Each input serialized
-        for input in &self.inputs {
-            bytes.extend_from_slice(&input.to_bytes());
-        }
+impl Encodable for TransactionOutput {
+    fn encode(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+}
 
-        // Lock time (4 bytes LE)
-        bytes.extend_from_slice(&self.lock_time.to_le_bytes());
+impl WireFormat for TransactionOutput {
+    fn wire_size(&self) -> usize {
+        8 + self.script_pubkey.wire_size()
+    }
+}
 
-        bytes
+impl Decodable for BitcoinTransaction {
+    const MIN_ENCODED_LEN: usize = 10;
+
+    fn decode(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut reader = BitcoinReader::new(bytes);
+        let tx = reader
+            .read_transaction()
+            .map_err(|e| e.at(reader.position()))?;
+        Ok((tx, reader.position()))
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
-        let mut offset = 0;
+    fn read_from(reader: &mut BitcoinReader<'_>) -> Result<Self, BitcoinError> {
+        reader.read_transaction()
+    }
+}
 
-        // Read version (4 bytes LE)
-        if bytes.len() < 4 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
-        let version = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-        offset += 4;
+impl Encodable for BitcoinTransaction {
+    fn encode(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+}
 
-        // Read CompactSize for input count
-        let (input_count, consumed) = CompactSize::from_bytes(&bytes[offset..])?;
-        offset += consumed;
+impl WireFormat for BitcoinTransaction {
+    fn wire_size(&self) -> usize {
+        self.serialized_len()
+    }
+}
 
-        // Parse inputs one by one
-        let mut inputs = Vec::new();
-        for _ in 0..input_count.value {
-            let (input, consumed) = TransactionInput::from_bytes(&bytes[offset..])?;
-            inputs.push(input);
-            offset += consumed;
-        }
+// Length-prefixed byte string, as used for witness items
+impl Decodable for Vec<u8> {
+    fn decode(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        let mut reader = BitcoinReader::new(bytes);
+        let item = Self::read_from(&mut reader)?;
+        Ok((item, reader.position()))
+    }
 
-        // Read final 4 bytes for lock_time
-        if bytes.len() < offset + 4 {
-            return Err(BitcoinError::InsufficientBytes);
-        }
-        let lock_time = u32::from_le_bytes([
-            bytes[offset],
-            bytes[offset + 1],
-            bytes[offset + 2],
-            bytes[offset + 3],
-        ]);
-        offset += 4;
+    fn read_from(reader: &mut BitcoinReader<'_>) -> Result<Self, BitcoinError> {
+        Ok(reader.read_var_bytes()?.to_vec())
+    }
+}
 
-        Ok((BitcoinTransaction::new(version, inputs, lock_time), offset))
+/// Separates "nothing was read at all" from a truncated buffer
+fn ensure_non_empty(bytes: &[u8]) -> Result<(), BitcoinError> {
+    if bytes.is_empty() {
+        return Err(BitcoinError::EmptyInput);
     }
+    Ok(())
 }
 
-impl fmt::Display for BitcoinTransaction {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Bitcoin Transaction:")?;
-        writeln!(f, "  Version: {}", self.version)?;
-        writeln!(f, "  Inputs ({}): ", self.inputs.len())?;
+/// Decodes a value that must span all of `bytes`
+fn decode_exact<T: Decodable>(bytes: &[u8]) -> Result<T, BitcoinError> {
+    let (value, consumed) = T::decode(bytes)?;
+    if consumed != bytes.len() {
+        return Err(BitcoinError::InvalidFormat);
+    }
+    Ok(value)
+}
 
-        for (i, input) in self.inputs.iter().enumerate() {
-            writeln!(f, "    Input {}:", i)?;
-            writeln!(
-                f,
-                "      Previous Output Txid: {}",
-                hex::encode(&input.previous_output.txid.0)
-            )?;
-            writeln!(
-                f,
-                "      Previous Output Vout: {}",
-                input.previous_output.vout
-            )?;
-            writeln!(
-                f,
-                "      Script Sig Length: {}",
-                input.script_sig.bytes.len()
-            )?;
-            writeln!(
-                f,
-                "      Script Sig: {}",
-                hex::encode(&input.script_sig.bytes)
-            )?;
-            writeln!(f, "      Sequence: 0x{:08X}", input.sequence)?;
-        }
+// `TryFrom` conversions decode the whole buffer and reject trailing bytes
+// with `InvalidFormat`, unlike `from_bytes` which reports what it consumed
+macro_rules! impl_try_from_bytes {
+    ($($ty:ty),*) => {
+        $(
+            impl TryFrom<&[u8]> for $ty {
+                type Error = BitcoinError;
 
-        write!(f, "  Lock Time: {}", self.lock_time)
+                fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+                    decode_exact(bytes)
+                }
+            }
+
+            impl TryFrom<Vec<u8>> for $ty {
+                type Error = BitcoinError;
+
+                fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+                    decode_exact(&bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_bytes!(
+    CompactSize,
+    OutPoint,
+    Script,
+    TransactionInput,
+    TransactionOutput,
+    BitcoinTransaction
+);
+
+fn read_array<const N: usize>(bytes: &[u8], offset: usize) -> Result<[u8; N], BitcoinError> {
+    let end = offset
+        .checked_add(N)
+        .ok_or(BitcoinError::InsufficientBytes)?;
+    let slice = bytes
+        .get(offset..end)
+        .ok_or(BitcoinError::InsufficientBytes)?;
+    Ok(slice.try_into().expect("slice has length N"))
+}
+
+/// Reads a little-endian u16 at `offset`, `InsufficientBytes` if it runs past the end
+pub fn read_u16_le(bytes: &[u8], offset: usize) -> Result<u16, BitcoinError> {
+    read_array(bytes, offset).map(u16::from_le_bytes)
+}
+
+pub fn read_u32_le(bytes: &[u8], offset: usize) -> Result<u32, BitcoinError> {
+    read_array(bytes, offset).map(u32::from_le_bytes)
+}
+
+pub fn read_u64_le(bytes: &[u8], offset: usize) -> Result<u64, BitcoinError> {
+    read_array(bytes, offset).map(u64::from_le_bytes)
+}
+
+/// Cursor over a byte slice that decodes consensus-encoded fields and
+/// advances past them.
+///
+/// No method panics on any input: every read is bounds-checked and failures
+/// are returned as `BitcoinError`, and allocations are sized by what the
+/// remaining bytes could actually hold. The `from_bytes` parsers are built on
+/// this and share the guarantee, so they are safe to use on untrusted data.
+#[derive(Debug, Clone, Copy)]
+pub struct BitcoinReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    limits: Limits,
+}
+
+/// Default cap on scriptSig and scriptPubKey length (the standardness limit)
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// Anti-DoS caps applied while parsing; exceeding a count or script cap
+/// gives `InvalidFormat`. Witness stacks have no cap of their own and are
+/// bounded only by `max_tx_size`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Limits {
+    pub max_inputs: usize,
+    pub max_outputs: usize,
+    pub max_script_len: usize,
+    /// Serialized size of one transaction, witness included. The reader
+    /// sees only this many bytes from the start of the transaction, so a
+    /// longer one fails at the cutoff before anything past it is allocated.
+    pub max_tx_size: usize,
+}
+
+/// As many inputs or outputs as fit in `MAX_TX_SIZE`, and `MAX_SCRIPT_SIZE`
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_inputs: MAX_TX_SIZE / TransactionInput::MIN_ENCODED_LEN,
+            max_outputs: MAX_TX_SIZE / TransactionOutput::MIN_ENCODED_LEN,
+            max_script_len: MAX_SCRIPT_SIZE,
+            max_tx_size: MAX_TX_SIZE,
+        }
+    }
+}
+
+impl<'a> BitcoinReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        BitcoinReader {
+            bytes,
+            position: 0,
+            limits: Limits::default(),
+        }
+    }
+
+    /// Overrides the script length cap applied by `read_script`
+    pub fn with_max_script_len(mut self, max_script_len: usize) -> Self {
+        self.limits.max_script_len = max_script_len;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len().saturating_sub(self.position)
+    }
+
+    // A custom `Decodable::read_from` could push the position past the end
+    fn rest(&self) -> &'a [u8] {
+        self.bytes.get(self.position..).unwrap_or(&[])
+    }
+
+    /// Returns the next `n` bytes without advancing
+    pub fn peek(&self, n: usize) -> Option<&'a [u8]> {
+        self.bytes.get(self.position..self.position.checked_add(n)?)
+    }
+
+    pub fn read_exact(&mut self, n: usize) -> Result<&'a [u8], BitcoinError> {
+        let slice = self.peek(n).ok_or(BitcoinError::InsufficientBytes)?;
+        self.position += n;
+        Ok(slice)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, BitcoinError> {
+        read_array::<1>(self.bytes, self.position).map(|[byte]| {
+            self.position += 1;
+            byte
+        })
+    }
+
+    pub fn read_u16_le(&mut self) -> Result<u16, BitcoinError> {
+        let value = read_u16_le(self.bytes, self.position)?;
+        self.position += 2;
+        Ok(value)
+    }
+
+    pub fn read_u32_le(&mut self) -> Result<u32, BitcoinError> {
+        let value = read_u32_le(self.bytes, self.position)?;
+        self.position += 4;
+        Ok(value)
+    }
+
+    pub fn read_u64_le(&mut self) -> Result<u64, BitcoinError> {
+        let value = read_u64_le(self.bytes, self.position)?;
+        self.position += 8;
+        Ok(value)
+    }
+
+    pub fn read_compact_size(&mut self) -> Result<CompactSize, BitcoinError> {
+        let mut lookahead = *self;
+        let compact = lookahead.read_compact_size_unchecked()?;
+
+        // Reject encodings that use a wider prefix than the value requires
+        let minimal = match lookahead.position - self.position {
+            3 => compact.value >= 0xFD,
+            5 => compact.value > 0xFFFF,
+            9 => compact.value > 0xFFFFFFFF,
+            _ => true,
+        };
+        if !minimal {
+            return Err(BitcoinError::InvalidFormat);
+        }
+
+        *self = lookahead;
+        Ok(compact)
+    }
+
+    pub fn read_compact_size_unchecked(&mut self) -> Result<CompactSize, BitcoinError> {
+        // Read through a copy so a truncated value leaves the position untouched
+        let mut lookahead = *self;
+        let value = match lookahead.read_u8()? {
+            0xFD => lookahead.read_u16_le()? as u64,
+            0xFE => lookahead.read_u32_le()? as u64,
+            0xFF => lookahead.read_u64_le()?,
+            prefix => prefix as u64,
+        };
+        *self = lookahead;
+        Ok(CompactSize::new(value))
+    }
+
+    /// Reads a CompactSize count of items that each take at least
+    /// `min_item_size` bytes, rejecting counts the remaining bytes cannot hold
+    pub fn read_count(&mut self, min_item_size: usize) -> Result<usize, BitcoinError> {
+        let mut lookahead = *self;
+        let count = lookahead.read_compact_size()?.value;
+        if count > (lookahead.remaining() / min_item_size.max(1)) as u64 {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        *self = lookahead;
+        Ok(count as usize)
+    }
+
+    /// Reads CompactSize-prefixed bytes without any length cap
+    fn read_var_bytes(&mut self) -> Result<&'a [u8], BitcoinError> {
+        let mut lookahead = *self;
+        let length = lookahead.read_count(1)?;
+        let bytes = lookahead.read_exact(length)?;
+        *self = lookahead;
+        Ok(bytes)
+    }
+
+    pub fn read_script(&mut self) -> Result<Script, BitcoinError> {
+        Ok(self.read_script_ref()?.to_script())
+    }
+
+    /// Like `read_script`, but borrows the script bytes from the buffer
+    pub fn read_script_ref(&mut self) -> Result<ScriptRef<'a>, BitcoinError> {
+        let mut lookahead = *self;
+        if lookahead.read_compact_size()?.value > self.limits.max_script_len as u64 {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(ScriptRef(self.read_var_bytes()?))
+    }
+
+    /// Reads a CompactSize count and then that many items, rejecting counts
+    /// that could not fit in the remaining bytes
+    pub fn read_vec<T: Decodable>(&mut self) -> Result<Vec<T>, BitcoinError> {
+        let count = self.read_count(T::MIN_ENCODED_LEN)?;
+        let mut items = Vec::with_capacity(count);
+        for _ in 0..count {
+            items.push(T::read_from(self)?);
+        }
+        Ok(items)
+    }
+
+    pub fn read_outpoint(&mut self) -> Result<OutPoint, BitcoinError> {
+        let mut txid = [0u8; 32];
+        txid.copy_from_slice(self.read_exact(32)?);
+        let vout = self.read_u32_le()?;
+        Ok(OutPoint::new(txid, vout))
+    }
+
+    pub fn read_input(&mut self) -> Result<TransactionInput, BitcoinError> {
+        let previous_output = self.read_outpoint()?;
+        let script_sig = self.read_script()?;
+        let sequence = self.read_u32_le()?;
+        Ok(TransactionInput::new(previous_output, script_sig, sequence))
+    }
+
+    pub fn read_output(&mut self) -> Result<TransactionOutput, BitcoinError> {
+        let value = self.read_u64_le()?;
+        let script_pubkey = self.read_script()?;
+        Ok(TransactionOutput::new(value, script_pubkey))
+    }
+
+    pub fn read_input_ref(&mut self) -> Result<TransactionInputRef<'a>, BitcoinError> {
+        let previous_output = self.read_outpoint()?;
+        let script_sig = self.read_script_ref()?;
+        let sequence = self.read_u32_le()?;
+        Ok(TransactionInputRef {
+            previous_output,
+            script_sig,
+            sequence,
+        })
+    }
+
+    pub fn read_output_ref(&mut self) -> Result<TransactionOutputRef<'a>, BitcoinError> {
+        let value = self.read_u64_le()?;
+        let script_pubkey = self.read_script_ref()?;
+        Ok(TransactionOutputRef {
+            value,
+            script_pubkey,
+        })
+    }
+
+    pub fn read_transaction(&mut self) -> Result<BitcoinTransaction, BitcoinError> {
+        Ok(self.read_transaction_ref()?.to_transaction())
+    }
+
+    /// Like `read_transaction`, but scripts and witness items borrow from
+    /// the buffer instead of being copied
+    pub fn read_transaction_ref(&mut self) -> Result<TransactionRef<'a>, BitcoinError> {
+        self.read_transaction_ref_as(SerializeMode::Auto)
+    }
+
+    /// Like `read_transaction_ref`, with the marker interpretation forced
+    /// by `mode`
+    pub fn read_transaction_ref_as(
+        &mut self,
+        mode: SerializeMode,
+    ) -> Result<TransactionRef<'a>, BitcoinError> {
+        self.within_tx_size(|reader| reader.read_transaction_fields(mode))
+    }
+
+    fn read_transaction_fields(
+        &mut self,
+        mode: SerializeMode,
+    ) -> Result<TransactionRef<'a>, BitcoinError> {
+        let version = self.read_u32_le()?;
+        let segwit = self.read_segwit_marker(mode)?;
+
+        let count = self.read_input_count(segwit)?;
+        let mut inputs = Vec::with_capacity(count);
+        for _ in 0..count {
+            inputs.push(self.read_input_ref()?);
+        }
+
+        let count = self.read_output_count()?;
+        let mut outputs = Vec::with_capacity(count);
+        for _ in 0..count {
+            outputs.push(self.read_output_ref()?);
+        }
+
+        // One witness stack per input
+        let mut witnesses = Vec::new();
+        if segwit {
+            let witness_start = self.position;
+            for _ in 0..inputs.len() {
+                // Witness items are not subject to the script length cap
+                let count = self.read_count(1)?;
+                let mut stack = Vec::with_capacity(count);
+                for _ in 0..count {
+                    stack.push(self.read_var_bytes()?);
+                }
+                witnesses.push(stack);
+            }
+
+            // The marker and flag must not be used without any witness data
+            if witnesses.iter().all(Vec::is_empty) {
+                self.position = witness_start;
+                return Err(BitcoinError::InvalidFormat);
+            }
+        }
+
+        let lock_time = self.read_u32_le()?;
+
+        Ok(TransactionRef {
+            version,
+            inputs,
+            outputs,
+            lock_time,
+            witnesses,
+        })
+    }
+
+    /// Like `read_transaction`, but decodes into `out`, reusing the
+    /// allocations of its inputs, outputs, scripts and witness items. On
+    /// error `out` holds a partially decoded transaction.
+    pub fn read_transaction_into(
+        &mut self,
+        out: &mut BitcoinTransaction,
+    ) -> Result<(), BitcoinError> {
+        self.within_tx_size(|reader| reader.read_transaction_fields_into(out))
+    }
+
+    fn read_transaction_fields_into(
+        &mut self,
+        out: &mut BitcoinTransaction,
+    ) -> Result<(), BitcoinError> {
+        out.version = self.read_u32_le()?;
+        let segwit = self.read_segwit_marker(SerializeMode::Auto)?;
+
+        let count = self.read_input_count(segwit)?;
+        out.inputs.truncate(count);
+        for index in 0..count {
+            let input = self.read_input_ref()?;
+            match out.inputs.get_mut(index) {
+                Some(slot) => {
+                    slot.previous_output = input.previous_output;
+                    slot.script_sig.bytes.clear();
+                    slot.script_sig.bytes.extend_from_slice(input.script_sig.0);
+                    slot.sequence = input.sequence;
+                }
+                None => out.inputs.push(input.to_input()),
+            }
+        }
+
+        let count = self.read_output_count()?;
+        out.outputs.truncate(count);
+        for index in 0..count {
+            let output = self.read_output_ref()?;
+            match out.outputs.get_mut(index) {
+                Some(slot) => {
+                    slot.value = output.value;
+                    slot.script_pubkey.bytes.clear();
+                    slot.script_pubkey
+                        .bytes
+                        .extend_from_slice(output.script_pubkey.0);
+                }
+                None => out.outputs.push(output.to_output()),
+            }
+        }
+
+        if segwit {
+            let witness_start = self.position;
+            out.witnesses.truncate(out.inputs.len());
+            out.witnesses.resize(out.inputs.len(), Vec::new());
+            for stack in &mut out.witnesses {
+                let count = self.read_count(1)?;
+                stack.truncate(count);
+                for index in 0..count {
+                    let item = self.read_var_bytes()?;
+                    match stack.get_mut(index) {
+                        Some(slot) => {
+                            slot.clear();
+                            slot.extend_from_slice(item);
+                        }
+                        None => stack.push(item.to_vec()),
+                    }
+                }
+            }
+            if out.witnesses.iter().all(Vec::is_empty) {
+                self.position = witness_start;
+                return Err(BitcoinError::InvalidFormat);
+            }
+        } else {
+            out.witnesses.clear();
+        }
+
+        out.lock_time = self.read_u32_le()?;
+        Ok(())
+    }
+
+    // Disambiguation in `Auto` mode: `00 01` after the version is always
+    // the segwit marker and flag. `00` followed by any other byte is a
+    // legacy transaction with zero inputs and that many outputs. This is
+    // more lenient than Bitcoin Core, which takes any nonzero byte there as
+    // optional-data flags and rejects all but 0x01. A legacy zero-input
+    // transaction with exactly one output is unrepresentable, and a segwit
+    // one with zero inputs is rejected (in `read_input_count`) since it has
+    // no input to carry a witness.
+    fn read_segwit_marker(&mut self, mode: SerializeMode) -> Result<bool, BitcoinError> {
+        let has_marker = self.peek(2) == Some(&[0x00, 0x01]);
+        let segwit = match mode {
+            SerializeMode::Auto => has_marker,
+            SerializeMode::Legacy => false,
+            SerializeMode::Segwit if has_marker => true,
+            SerializeMode::Segwit => return Err(BitcoinError::InvalidFormat),
+        };
+        if segwit {
+            self.position += 2;
+        }
+        Ok(segwit)
+    }
+
+    fn read_input_count(&mut self, segwit: bool) -> Result<usize, BitcoinError> {
+        let count_start = self.position;
+        let count = self.read_count(TransactionInput::MIN_ENCODED_LEN)?;
+        if segwit && count == 0 || count > self.limits.max_inputs {
+            self.position = count_start;
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(count)
+    }
+
+    fn read_output_count(&mut self) -> Result<usize, BitcoinError> {
+        let count_start = self.position;
+        let count = self.read_count(TransactionOutput::MIN_ENCODED_LEN)?;
+        if count > self.limits.max_outputs {
+            self.position = count_start;
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(count)
+    }
+
+    // Runs `read` with the buffer cut off `max_tx_size` bytes past the
+    // current position, so counts and lengths are checked against the window
+    fn within_tx_size<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, BitcoinError>,
+    ) -> Result<T, BitcoinError> {
+        let full = self.bytes;
+        let end = self
+            .position
+            .saturating_add(self.limits.max_tx_size)
+            .min(full.len());
+        self.bytes = &full[..end];
+        let result = read(self);
+        self.bytes = full;
+        result
+    }
+}
+
+/// How to treat a 0x00 byte after the version when parsing a transaction
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum SerializeMode {
+    /// `00 01` is the segwit marker and flag, anything else is legacy
+    #[default]
+    Auto,
+    /// Never a marker: 0x00 is always a zero input count
+    Legacy,
+    /// The marker and flag are required
+    Segwit,
+}
+
+/// Highest transaction version relayed by default (3 is TRUC, BIP-431)
+pub const MAX_STANDARD_TX_VERSION: u32 = 3;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct Version(pub u32);
+
+impl Version {
+    /// Versions 1 through `MAX_STANDARD_TX_VERSION`. Consensus accepts any
+    /// value, so this is only a policy check.
+    pub fn is_standard(&self) -> bool {
+        (1..=MAX_STANDARD_TX_VERSION).contains(&self.0)
+    }
+}
+
+impl From<u32> for Version {
+    fn from(version: u32) -> Self {
+        Version(version)
+    }
+}
+
+/// An input's nSequence, which also carries BIP-68 relative lock times
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct Sequence(pub u32);
+
+impl Sequence {
+    /// No relative lock time, no RBF signaling, lock_time ignored
+    pub const FINAL: Sequence = Sequence(0xFFFF_FFFF);
+    /// Highest value that signals BIP-125 replaceability
+    pub const ENABLE_RBF: Sequence = Sequence(0xFFFF_FFFD);
+
+    const DISABLE_FLAG: u32 = 1 << 31;
+    const TYPE_FLAG: u32 = 1 << 22;
+    const VALUE_MASK: u32 = 0xFFFF;
+
+    /// True when the disable bit is clear. Consensus only enforces this for
+    /// transactions with version 2 or higher.
+    pub fn is_relative_lock_time(&self) -> bool {
+        self.0 & Self::DISABLE_FLAG == 0
+    }
+
+    /// Relative lock in blocks, if enabled and height-based
+    pub fn relative_blocks(&self) -> Option<u16> {
+        (self.is_relative_lock_time() && self.0 & Self::TYPE_FLAG == 0)
+            .then_some((self.0 & Self::VALUE_MASK) as u16)
+    }
+
+    /// Relative lock in seconds, if enabled and time-based. The encoded
+    /// value counts 512-second units.
+    pub fn relative_seconds(&self) -> Option<u32> {
+        (self.is_relative_lock_time() && self.0 & Self::TYPE_FLAG != 0)
+            .then_some((self.0 & Self::VALUE_MASK) * 512)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Hash, PartialOrd, Ord)]
+pub struct Txid(pub [u8; 32]);
+
+impl Txid {
+    pub const ZERO: Txid = Txid([0u8; 32]);
+
+    /// Hex in display order (reversed), as used by block explorers and RPC
+    pub fn to_display_hex(&self) -> String {
+        let mut bytes = self.0;
+        bytes.reverse();
+        hex::encode(bytes)
+    }
+
+    /// Parses a txid written in display order (reversed)
+    pub fn from_display_hex(s: &str) -> Result<Self, BitcoinError> {
+        let bytes = hex::decode(s).map_err(|_| BitcoinError::InvalidFormat)?;
+        let mut array: [u8; 32] = bytes.try_into().map_err(|_| BitcoinError::InvalidFormat)?;
+        array.reverse();
+        Ok(Txid(array))
+    }
+
+    /// Equality that inspects every byte regardless of where the first
+    /// difference is, for comparing secret-derived values
+    pub fn ct_eq(&self, other: &Txid) -> bool {
+        let diff = self
+            .0
+            .iter()
+            .zip(other.0.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        // Keep the optimizer from turning the fold back into an early exit
+        core::hint::black_box(diff) == 0
+    }
+}
+
+// Serde uses internal byte order, i.e. the bytes exactly as they appear on the
+// wire. Use `to_display_hex` for the reversed order shown by explorers.
+impl Serialize for Txid {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let hex_string = hex::encode(self.0);
+        serializer.serialize_str(&hex_string)
+    }
+}
+
+impl<'de> Deserialize<'de> for Txid {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let hex_string = String::deserialize(deserializer)?;
+        let bytes = hex::decode(&hex_string).map_err(serde::de::Error::custom)?;
+        if bytes.len() != 32 {
+            return Err(serde::de::Error::custom("Txid must be exactly 32 bytes"));
+        }
+        let mut array = [0u8; 32];
+        array.copy_from_slice(&bytes);
+        Ok(Txid(array))
+    }
+}
+
+// Ordered by txid bytes, then vout
+#[derive(Debug, Default, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OutPoint {
+    pub txid: Txid,
+    pub vout: u32,
+}
+
+impl OutPoint {
+    /// The null outpoint spent by coinbase inputs: zero txid, vout 0xFFFFFFFF
+    pub const COINBASE: OutPoint = OutPoint {
+        txid: Txid::ZERO,
+        vout: u32::MAX,
+    };
+
+    pub fn new(txid: [u8; 32], vout: u32) -> Self {
+        OutPoint {
+            txid: Txid(txid),
+            vout,
+        }
+    }
+
+    pub fn null() -> Self {
+        OutPoint::COINBASE
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == OutPoint::COINBASE
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(36);
+        self.consensus_encode(&mut bytes)
+            .expect("writing to a Vec never fails");
+        bytes
+    }
+
+    pub fn consensus_encode<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
+        w.write_all(&self.txid.0)?;
+        w.write_all(&self.vout.to_le_bytes())?;
+        Ok(36)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        ensure_non_empty(bytes)?;
+        <Self as Decodable>::decode(bytes)
+    }
+
+    /// A CompactSize count followed by that many 36-byte outpoints. Counts
+    /// that cannot fit in the remaining bytes are rejected before allocating.
+    pub fn decode_vec(bytes: &[u8]) -> Result<(Vec<OutPoint>, usize), BitcoinError> {
+        decode_vec(bytes)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Script {
+    pub bytes: Vec<u8>,
+}
+
+impl Script {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Script { bytes }
+    }
+
+    /// `OP_DUP OP_HASH160 <pubkey_hash> OP_EQUALVERIFY OP_CHECKSIG`
+    pub fn p2pkh(pubkey_hash: &[u8; 20]) -> Self {
+        use opcodes::*;
+
+        let mut bytes = vec![OP_DUP, OP_HASH160, 0x14];
+        bytes.extend_from_slice(pubkey_hash);
+        bytes.extend_from_slice(&[OP_EQUALVERIFY, OP_CHECKSIG]);
+        Script::new(bytes)
+    }
+
+    /// `OP_HASH160 <script_hash> OP_EQUAL`
+    pub fn p2sh(script_hash: &[u8; 20]) -> Self {
+        use opcodes::*;
+
+        let mut bytes = vec![OP_HASH160, 0x14];
+        bytes.extend_from_slice(script_hash);
+        bytes.push(OP_EQUAL);
+        Script::new(bytes)
+    }
+
+    /// `OP_0 <pubkey_hash>`
+    pub fn p2wpkh(pubkey_hash: &[u8; 20]) -> Self {
+        Script::witness_program(opcodes::OP_0, pubkey_hash)
+    }
+
+    /// `OP_0 <witness_program>`, the SHA-256 of the witness script
+    pub fn p2wsh(witness_program: &[u8; 32]) -> Self {
+        Script::witness_program(opcodes::OP_0, witness_program)
+    }
+
+    /// `OP_1 <output_key>`, the x-only tweaked key
+    pub fn p2tr(output_key: &[u8; 32]) -> Self {
+        Script::witness_program(opcodes::OP_1, output_key)
+    }
+
+    fn witness_program(version_op: u8, program: &[u8]) -> Self {
+        let mut bytes = Vec::with_capacity(2 + program.len());
+        bytes.push(version_op);
+        bytes.push(program.len() as u8);
+        bytes.extend_from_slice(program);
+        Script::new(bytes)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(9 + self.bytes.len());
+        self.consensus_encode(&mut result)
+            .expect("writing to a Vec never fails");
+        result
+    }
+
+    pub fn consensus_encode<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
+        let length = CompactSize::from_len(&self.bytes);
+        let written = length.consensus_encode(w)?;
+        w.write_all(&self.bytes)?;
+        Ok(written + self.bytes.len())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        ensure_non_empty(bytes)?;
+        <Self as Decodable>::decode(bytes)
+    }
+}
+
+impl Deref for Script {
+    type Target = Vec<u8>;
+    fn deref(&self) -> &Self::Target {
+        &self.bytes
+    }
+}
+
+pub mod opcodes {
+    pub const OP_0: u8 = 0x00;
+    pub const OP_PUSHDATA1: u8 = 0x4c;
+    pub const OP_PUSHDATA2: u8 = 0x4d;
+    pub const OP_PUSHDATA4: u8 = 0x4e;
+    pub const OP_1NEGATE: u8 = 0x4f;
+    pub const OP_1: u8 = 0x51;
+    pub const OP_16: u8 = 0x60;
+    pub const OP_RETURN: u8 = 0x6a;
+    pub const OP_DUP: u8 = 0x76;
+    pub const OP_EQUAL: u8 = 0x87;
+    pub const OP_EQUALVERIFY: u8 = 0x88;
+    pub const OP_HASH160: u8 = 0xa9;
+    pub const OP_CHECKSIG: u8 = 0xac;
+}
+
+fn opcode_name(opcode: u8) -> Option<&'static str> {
+    let name = match opcode {
+        0x4f => "OP_1NEGATE",
+        0x50 => "OP_RESERVED",
+        0x51 => "OP_1",
+        0x52 => "OP_2",
+        0x53 => "OP_3",
+        0x54 => "OP_4",
+        0x55 => "OP_5",
+        0x56 => "OP_6",
+        0x57 => "OP_7",
+        0x58 => "OP_8",
+        0x59 => "OP_9",
+        0x5a => "OP_10",
+        0x5b => "OP_11",
+        0x5c => "OP_12",
+        0x5d => "OP_13",
+        0x5e => "OP_14",
+        0x5f => "OP_15",
+        0x60 => "OP_16",
+        0x61 => "OP_NOP",
+        0x63 => "OP_IF",
+        0x64 => "OP_NOTIF",
+        0x67 => "OP_ELSE",
+        0x68 => "OP_ENDIF",
+        0x69 => "OP_VERIFY",
+        0x6a => "OP_RETURN",
+        0x6b => "OP_TOALTSTACK",
+        0x6c => "OP_FROMALTSTACK",
+        0x6d => "OP_2DROP",
+        0x6e => "OP_2DUP",
+        0x6f => "OP_3DUP",
+        0x70 => "OP_2OVER",
+        0x71 => "OP_2ROT",
+        0x72 => "OP_2SWAP",
+        0x73 => "OP_IFDUP",
+        0x74 => "OP_DEPTH",
+        0x75 => "OP_DROP",
+        0x76 => "OP_DUP",
+        0x77 => "OP_NIP",
+        0x78 => "OP_OVER",
+        0x79 => "OP_PICK",
+        0x7a => "OP_ROLL",
+        0x7b => "OP_ROT",
+        0x7c => "OP_SWAP",
+        0x7d => "OP_TUCK",
+        0x82 => "OP_SIZE",
+        0x87 => "OP_EQUAL",
+        0x88 => "OP_EQUALVERIFY",
+        0x8b => "OP_1ADD",
+        0x8c => "OP_1SUB",
+        0x8f => "OP_NEGATE",
+        0x90 => "OP_ABS",
+        0x91 => "OP_NOT",
+        0x92 => "OP_0NOTEQUAL",
+        0x93 => "OP_ADD",
+        0x94 => "OP_SUB",
+        0x9a => "OP_BOOLAND",
+        0x9b => "OP_BOOLOR",
+        0x9c => "OP_NUMEQUAL",
+        0x9d => "OP_NUMEQUALVERIFY",
+        0x9e => "OP_NUMNOTEQUAL",
+        0x9f => "OP_LESSTHAN",
+        0xa0 => "OP_GREATERTHAN",
+        0xa1 => "OP_LESSTHANOREQUAL",
+        0xa2 => "OP_GREATERTHANOREQUAL",
+        0xa3 => "OP_MIN",
+        0xa4 => "OP_MAX",
+        0xa5 => "OP_WITHIN",
+        0xa6 => "OP_RIPEMD160",
+        0xa7 => "OP_SHA1",
+        0xa8 => "OP_SHA256",
+        0xa9 => "OP_HASH160",
+        0xaa => "OP_HASH256",
+        0xab => "OP_CODESEPARATOR",
+        0xac => "OP_CHECKSIG",
+        0xad => "OP_CHECKSIGVERIFY",
+        0xae => "OP_CHECKMULTISIG",
+        0xaf => "OP_CHECKMULTISIGVERIFY",
+        0xb0 => "OP_NOP1",
+        0xb1 => "OP_CHECKLOCKTIMEVERIFY",
+        0xb2 => "OP_CHECKSEQUENCEVERIFY",
+        0xba => "OP_CHECKSIGADD",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Instruction<'a> {
+    // Data push, with the opcode that produced it (OP_0 pushes empty data)
+    Push { opcode: u8, data: &'a [u8] },
+    Op(u8),
+}
+
+/// Iterator over the instructions of a script; yields `InvalidFormat` once
+/// and stops if a push runs past the end of the script
+#[derive(Debug, Clone)]
+pub struct Instructions<'a> {
+    reader: BitcoinReader<'a>,
+    done: bool,
+}
+
+impl<'a> Instructions<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Instructions {
+            reader: BitcoinReader::new(bytes),
+            done: false,
+        }
+    }
+
+    fn read_instruction(&mut self) -> Result<Instruction<'a>, BitcoinError> {
+        let opcode = self.reader.read_u8()?;
+        let len = match opcode {
+            0x01..=0x4b => opcode as usize,
+            opcodes::OP_PUSHDATA1 => self.reader.read_u8()? as usize,
+            opcodes::OP_PUSHDATA2 => self.reader.read_u16_le()? as usize,
+            opcodes::OP_PUSHDATA4 => self.reader.read_u32_le()? as usize,
+            opcodes::OP_0 => 0,
+            _ => return Ok(Instruction::Op(opcode)),
+        };
+        let data = self.reader.read_exact(len)?;
+        Ok(Instruction::Push { opcode, data })
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<Instruction<'a>, BitcoinError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.reader.remaining() == 0 {
+            return None;
+        }
+        let result = self
+            .read_instruction()
+            .map_err(|_| BitcoinError::InvalidFormat);
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+impl Script {
+    pub fn as_script_ref(&self) -> ScriptRef<'_> {
+        ScriptRef(&self.bytes)
+    }
+
+    pub fn instructions(&self) -> Instructions<'_> {
+        self.as_script_ref().instructions()
+    }
+
+    /// See `ScriptRef::op_return_data`
+    pub fn op_return_data(&self) -> Option<&[u8]> {
+        self.as_script_ref().op_return_data()
+    }
+
+    pub fn disasm(&self) -> Result<String, BitcoinError> {
+        self.as_script_ref().disasm()
+    }
+
+    pub fn script_type(&self) -> ScriptType {
+        self.as_script_ref().script_type()
+    }
+
+    pub fn has_minimal_pushes(&self) -> bool {
+        self.as_script_ref().has_minimal_pushes()
+    }
+
+    pub fn matches_template(&self, template: &[ScriptPattern]) -> Option<Vec<Vec<u8>>> {
+        self.as_script_ref().matches_template(template)
+    }
+
+    pub fn into_pushes(&self, skip_non_push: bool) -> Result<Vec<Vec<u8>>, BitcoinError> {
+        self.as_script_ref().into_pushes(skip_non_push)
+    }
+}
+
+/// A script borrowed from a larger buffer, e.g. by `BitcoinReader::read_script_ref`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ScriptRef<'a>(pub &'a [u8]);
+
+impl<'a> ScriptRef<'a> {
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Length-prefixed encoding, identical to `Script::to_bytes`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(9 + self.0.len());
+        CompactSize::from_len(self.0)
+            .consensus_encode(&mut result)
+            .expect("writing to a Vec never fails");
+        result.extend_from_slice(self.0);
+        result
+    }
+
+    pub fn to_script(&self) -> Script {
+        Script::new(self.0.to_vec())
+    }
+
+    pub fn instructions(&self) -> Instructions<'a> {
+        Instructions::new(self.0)
+    }
+
+    /// Payload of an OP_RETURN output: the data of the first push after
+    /// OP_RETURN (later pushes are ignored), or an empty slice for a bare
+    /// OP_RETURN. `None` if the script is not OP_RETURN or the byte after it
+    /// is not a well-formed push.
+    pub fn op_return_data(&self) -> Option<&'a [u8]> {
+        let rest = self.0.strip_prefix(&[opcodes::OP_RETURN])?;
+        match Instructions::new(rest).next() {
+            None => Some(&[]),
+            Some(Ok(Instruction::Push { data, .. })) => Some(data),
+            Some(_) => None,
+        }
+    }
+
+    /// Space-separated assembly, e.g. `OP_DUP OP_HASH160 OP_PUSHBYTES_20 <hex> ...`
+    pub fn disasm(&self) -> Result<String, BitcoinError> {
+        let mut parts = Vec::new();
+        for instruction in self.instructions() {
+            let part = match instruction? {
+                Instruction::Push {
+                    opcode: opcodes::OP_0,
+                    ..
+                } => "OP_0".to_string(),
+                Instruction::Push { opcode, data } => {
+                    let name = match opcode {
+                        opcodes::OP_PUSHDATA1 => "OP_PUSHDATA1".to_string(),
+                        opcodes::OP_PUSHDATA2 => "OP_PUSHDATA2".to_string(),
+                        opcodes::OP_PUSHDATA4 => "OP_PUSHDATA4".to_string(),
+                        n => format!("OP_PUSHBYTES_{}", n),
+                    };
+                    format!("{} {}", name, hex::encode(data))
+                }
+                Instruction::Op(opcode) => match opcode_name(opcode) {
+                    Some(name) => name.to_string(),
+                    None => format!("OP_UNKNOWN(0x{:02x})", opcode),
+                },
+            };
+            parts.push(part);
+        }
+        Ok(parts.join(" "))
+    }
+
+    /// Matches the script instruction by instruction against `template`,
+    /// returning the data of each `AnyPush` in order. `None` if the
+    /// instruction counts differ, any pattern fails, or the script is
+    /// malformed.
+    pub fn matches_template(&self, template: &[ScriptPattern]) -> Option<Vec<Vec<u8>>> {
+        let mut captures = Vec::new();
+        let mut patterns = template.iter();
+        for instruction in self.instructions() {
+            match (patterns.next()?, instruction.ok()?) {
+                (ScriptPattern::Op(expected), Instruction::Op(opcode)) if *expected == opcode => {}
+                // OP_0 is a push of nothing but written as a plain opcode
+                (ScriptPattern::Op(expected), Instruction::Push { opcode, data })
+                    if *expected == opcode && data.is_empty() => {}
+                (ScriptPattern::AnyPush, Instruction::Push { data, .. }) => {
+                    captures.push(data.to_vec());
+                }
+                _ => return None,
+            }
+        }
+        patterns.next().is_none().then_some(captures)
+    }
+
+    /// The stack items the script pushes, in order: push data, plus the
+    /// values of OP_1NEGATE and OP_1..OP_16. A P2PKH scriptSig gives
+    /// `[signature, pubkey]`. Other opcodes are skipped if `skip_non_push`,
+    /// otherwise they give `InvalidFormat`; so does a truncated push.
+    pub fn into_pushes(&self, skip_non_push: bool) -> Result<Vec<Vec<u8>>, BitcoinError> {
+        use opcodes::*;
+
+        let mut pushes = Vec::new();
+        for instruction in self.instructions() {
+            match instruction? {
+                Instruction::Push { data, .. } => pushes.push(data.to_vec()),
+                Instruction::Op(OP_1NEGATE) => pushes.push(vec![0x81]),
+                Instruction::Op(opcode @ OP_1..=OP_16) => pushes.push(vec![opcode - OP_1 + 1]),
+                Instruction::Op(_) if skip_non_push => {}
+                Instruction::Op(_) => return Err(BitcoinError::InvalidFormat),
+            }
+        }
+        Ok(pushes)
+    }
+
+    /// True if every push uses the smallest encoding for its data, as in
+    /// Bitcoin Core's `CheckMinimalPush`. False for truncated scripts.
+    pub fn has_minimal_pushes(&self) -> bool {
+        use opcodes::*;
+
+        self.instructions().all(|instruction| match instruction {
+            Ok(Instruction::Push { opcode, data }) => match data {
+                [] => opcode == OP_0,
+                // These have dedicated opcodes that are not pushes at all
+                [1..=16] | [0x81] => false,
+                _ if data.len() <= 0x4b => opcode as usize == data.len(),
+                _ if data.len() <= 0xFF => opcode == OP_PUSHDATA1,
+                _ if data.len() <= 0xFFFF => opcode == OP_PUSHDATA2,
+                _ => true,
+            },
+            Ok(Instruction::Op(_)) => true,
+            Err(_) => false,
+        })
+    }
+
+    pub fn script_type(&self) -> ScriptType {
+        use opcodes::*;
+
+        match self.0 {
+            [
+                OP_DUP,
+                OP_HASH160,
+                0x14,
+                hash @ ..,
+                OP_EQUALVERIFY,
+                OP_CHECKSIG,
+            ] if hash.len() == 20 => ScriptType::P2pkh,
+            [OP_HASH160, 0x14, hash @ .., OP_EQUAL] if hash.len() == 20 => ScriptType::P2sh,
+            [OP_0, 0x14, program @ ..] if program.len() == 20 => ScriptType::P2wpkh,
+            [OP_0, 0x20, program @ ..] if program.len() == 32 => ScriptType::P2wsh,
+            [OP_1, 0x20, program @ ..] if program.len() == 32 => ScriptType::P2tr,
+            [OP_RETURN, ..] => ScriptType::OpReturn,
+            _ => ScriptType::NonStandard,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    OpReturn,
+    NonStandard,
+}
+
+impl ScriptType {
+    /// Typical weight of a whole input spending this type, for fee
+    /// estimation: outpoint, scriptSig, sequence and witness. Assumes a
+    /// single signature of 72 bytes and a compressed key; P2SH is taken as
+    /// nested P2WPKH, P2WSH as `<pubkey> OP_CHECKSIG`, P2TR as a key path
+    /// spend. Unspendable and non-standard types give 0.
+    pub fn input_weight(&self) -> usize {
+        // Outpoint, sequence and scriptSig length byte, at 4 WU per byte
+        const BASE: usize = (36 + 4 + 1) * 4;
+        match self {
+            // <sig> <pubkey> in the scriptSig
+            ScriptType::P2pkh => BASE + (1 + 72 + 1 + 33) * 4,
+            // Push of the 22-byte P2WPKH program, then a P2WPKH witness
+            ScriptType::P2sh => BASE + 23 * 4 + (1 + 1 + 72 + 1 + 33),
+            ScriptType::P2wpkh => BASE + (1 + 1 + 72 + 1 + 33),
+            ScriptType::P2wsh => BASE + (1 + 1 + 72 + 1 + 35),
+            ScriptType::P2tr => BASE + (1 + 1 + 64),
+            ScriptType::OpReturn | ScriptType::NonStandard => 0,
+        }
+    }
+}
+
+impl fmt::Display for ScriptType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ScriptType::P2pkh => "P2PKH",
+            ScriptType::P2sh => "P2SH",
+            ScriptType::P2wpkh => "P2WPKH",
+            ScriptType::P2wsh => "P2WSH",
+            ScriptType::P2tr => "P2TR",
+            ScriptType::OpReturn => "OP_RETURN",
+            ScriptType::NonStandard => "non-standard",
+        })
+    }
+}
+
+/// Summary such as `P2WPKH (22 bytes): 0014751e76e8199196d4...`, showing at
+/// most the first 8 bytes. `{:#}` prints the full disassembly instead,
+/// falling back to hex for scripts that do not parse.
+impl fmt::Display for ScriptRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return match self.disasm() {
+                Ok(asm) => f.write_str(&asm),
+                Err(_) => f.write_str(&hex::encode(self.0)),
+            };
+        }
+
+        write!(f, "{} ({} bytes)", self.script_type(), self.0.len())?;
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        f.write_str(": ")?;
+        for byte in self.0.iter().take(8) {
+            write!(f, "{:02x}", byte)?;
+        }
+        if self.0.len() > 8 {
+            f.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.as_script_ref(), f)
+    }
+}
+
+/// One element of a template for `Script::matches_template`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScriptPattern {
+    /// This exact opcode, which must not push data (OP_0 matches `Op(OP_0)`)
+    Op(u8),
+    /// Any data push; its data is captured
+    AnyPush,
+}
+
+/// Largest data push that standard script execution accepts
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+
+#[derive(Debug, Clone, Default)]
+pub struct ScriptBuilder {
+    bytes: Vec<u8>,
+}
+
+impl ScriptBuilder {
+    pub fn new() -> Self {
+        ScriptBuilder::default()
+    }
+
+    pub fn push_opcode(mut self, opcode: u8) -> Self {
+        self.bytes.push(opcode);
+        self
+    }
+
+    /// Pushes `data` with the smallest push opcode that fits it. Data over
+    /// `MAX_SCRIPT_ELEMENT_SIZE` could never be executed, so it is rejected
+    /// with `InvalidFormat` rather than encoded with OP_PUSHDATA4.
+    pub fn push_slice(mut self, data: &[u8]) -> Result<Self, BitcoinError> {
+        match data.len() {
+            0 => self.bytes.push(opcodes::OP_0),
+            len @ 1..=0x4b => self.bytes.push(len as u8),
+            len @ 0x4c..=0xff => self.bytes.extend([opcodes::OP_PUSHDATA1, len as u8]),
+            len @ 0x100..=MAX_SCRIPT_ELEMENT_SIZE => {
+                self.bytes.push(opcodes::OP_PUSHDATA2);
+                self.bytes.extend((len as u16).to_le_bytes());
+            }
+            _ => return Err(BitcoinError::InvalidFormat),
+        }
+        self.bytes.extend_from_slice(data);
+        Ok(self)
+    }
+
+    /// Pushes a script number, using OP_0, OP_1NEGATE and OP_1..OP_16 where
+    /// possible and a minimal little-endian sign-magnitude push otherwise
+    pub fn push_int(mut self, n: i64) -> Self {
+        match n {
+            0 => self.push_opcode(opcodes::OP_0),
+            -1 => self.push_opcode(opcodes::OP_1NEGATE),
+            1..=16 => self.push_opcode(opcodes::OP_1 + (n as u8 - 1)),
+            _ => {
+                let mut magnitude = n.unsigned_abs();
+                let mut data = Vec::with_capacity(9);
+                while magnitude > 0 {
+                    data.push(magnitude as u8);
+                    magnitude >>= 8;
+                }
+                // The top bit carries the sign, so add a byte if it is taken
+                if data[data.len() - 1] & 0x80 != 0 {
+                    data.push(if n < 0 { 0x80 } else { 0x00 });
+                } else if n < 0 {
+                    let last = data.len() - 1;
+                    data[last] |= 0x80;
+                }
+                self.bytes.push(data.len() as u8);
+                self.bytes.extend_from_slice(&data);
+                self
+            }
+        }
+    }
+
+    pub fn into_script(self) -> Script {
+        Script::new(self.bytes)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Regtest,
+    Signet,
+}
+
+impl Network {
+    /// Message start bytes of the p2p protocol, in wire order
+    pub fn magic(self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0xf9, 0xbe, 0xb4, 0xd9],
+            Network::Testnet => [0x0b, 0x11, 0x09, 0x07],
+            Network::Regtest => [0xfa, 0xbf, 0xb5, 0xda],
+            // The default signet; custom signets derive their own
+            Network::Signet => [0x0a, 0x03, 0xcf, 0x40],
+        }
+    }
+
+    /// Base58check version bytes for (P2PKH, P2SH) addresses
+    pub fn address_version_bytes(self) -> (u8, u8) {
+        match self {
+            Network::Mainnet => (0x00, 0x05),
+            _ => (0x6f, 0xc4),
+        }
+    }
+
+    fn bech32_hrp(self) -> &'static str {
+        match self {
+            Network::Mainnet => "bc",
+            Network::Testnet | Network::Signet => "tb",
+            Network::Regtest => "bcrt",
+        }
+    }
+}
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+fn base58check_encode(version: u8, payload: &[u8]) -> String {
+    let mut data = Vec::with_capacity(1 + payload.len() + 4);
+    data.push(version);
+    data.extend_from_slice(payload);
+    let checksum = sha256d(&data);
+    data.extend_from_slice(&checksum[..4]);
+
+    // Base-58 digits, least significant first
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in &data {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    // Each leading zero byte is written as a '1'
+    let leading_zeros = data.iter().take_while(|&&byte| byte == 0).count();
+    let mut encoded = String::with_capacity(leading_zeros + digits.len());
+    encoded.extend(core::iter::repeat_n('1', leading_zeros));
+    encoded.extend(
+        digits
+            .iter()
+            .rev()
+            .map(|&d| BASE58_ALPHABET[d as usize] as char),
+    );
+    encoded
+}
+
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut chk = 1u32;
+    for &value in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ value as u32;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+/// Segwit address: bech32 (BIP-173) for version 0, bech32m (BIP-350) above
+fn segwit_address(hrp: &str, version: u8, program: &[u8]) -> String {
+    // Regroup the program's 8-bit bytes into 5-bit values, zero-padding the end
+    let mut data = vec![version];
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &byte in program {
+        acc = ((acc << 8) | byte as u32) & 0xfff;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            data.push(((acc >> bits) & 31) as u8);
+        }
+    }
+    if bits > 0 {
+        data.push(((acc << (5 - bits)) & 31) as u8);
+    }
+
+    let constant = if version == 0 { 1 } else { 0x2bc830a3 };
+    let mut values: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|c| c & 31));
+    values.extend_from_slice(&data);
+    values.extend([0u8; 6]);
+    let checksum = bech32_polymod(&values) ^ constant;
+
+    let mut address = String::with_capacity(hrp.len() + 1 + data.len() + 6);
+    address.push_str(hrp);
+    address.push('1');
+    address.extend(data.iter().map(|&d| BECH32_CHARSET[d as usize] as char));
+    address.extend(
+        (0..6).map(|i| BECH32_CHARSET[((checksum >> (5 * (5 - i))) & 31) as usize] as char),
+    );
+    address
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct TransactionInput {
+    pub previous_output: OutPoint,
+    pub script_sig: Script,
+    pub sequence: u32,
+}
+
+/// Spends the zero txid at vout 0 with an empty scriptSig and a final sequence
+impl Default for TransactionInput {
+    fn default() -> Self {
+        TransactionInput::new(OutPoint::default(), Script::default(), 0xFFFF_FFFF)
+    }
+}
+
+impl TransactionInput {
+    pub fn new(previous_output: OutPoint, script_sig: Script, sequence: u32) -> Self {
+        TransactionInput {
+            previous_output,
+            script_sig,
+            sequence,
+        }
+    }
+
+    /// The raw `sequence` as a BIP-68 `Sequence`
+    pub fn sequence_typed(&self) -> Sequence {
+        Sequence(self.sequence)
+    }
+
+    /// True for an empty scriptSig, as in native segwit spends where all
+    /// unlocking data is in the witness. Nested segwit (P2SH-wrapped) inputs
+    /// still carry a redeem script push and return false.
+    pub fn is_witness_only(&self) -> bool {
+        self.script_sig.is_empty()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.consensus_encode(&mut bytes)
+            .expect("writing to a Vec never fails");
+        bytes
+    }
+
+    pub fn consensus_encode<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
+        let mut written = self.previous_output.consensus_encode(w)?;
+        written += self.script_sig.consensus_encode(w)?;
+        w.write_all(&self.sequence.to_le_bytes())?;
+        Ok(written + 4)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        ensure_non_empty(bytes)?;
+        <Self as Decodable>::decode(bytes)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct TransactionOutput {
+    pub value: u64,
+    pub script_pubkey: Script,
+}
+
+impl TransactionOutput {
+    pub fn new(value: u64, script_pubkey: Script) -> Self {
+        TransactionOutput {
+            value,
+            script_pubkey,
+        }
+    }
+
+    pub fn amount(&self) -> Amount {
+        Amount::from_sat(self.value)
+    }
+
+    /// Address paying to this output on `network`, `None` unless the
+    /// scriptPubKey is P2PKH, P2SH, P2WPKH, P2WSH or P2TR
+    pub fn address(&self, network: Network) -> Option<String> {
+        let script = &self.script_pubkey.bytes;
+        let address = match self.script_pubkey.script_type() {
+            ScriptType::P2pkh => {
+                base58check_encode(network.address_version_bytes().0, &script[3..23])
+            }
+            ScriptType::P2sh => {
+                base58check_encode(network.address_version_bytes().1, &script[2..22])
+            }
+            ScriptType::P2wpkh | ScriptType::P2wsh => {
+                segwit_address(network.bech32_hrp(), 0, &script[2..])
+            }
+            ScriptType::P2tr => segwit_address(network.bech32_hrp(), 1, &script[2..]),
+            ScriptType::OpReturn | ScriptType::NonStandard => return None,
+        };
+        Some(address)
+    }
+
+    /// Smallest value worth spending at `fee_rate_sat_per_vb`, following
+    /// Bitcoin Core's `GetDustThreshold`: the fee for this output plus a
+    /// typical input spending it. OP_RETURN outputs are unspendable and have
+    /// no threshold.
+    pub fn dust_threshold(&self, fee_rate_sat_per_vb: f64) -> u64 {
+        let spend_size = match self.script_pubkey.script_type() {
+            ScriptType::OpReturn => return 0,
+            // outpoint + scriptSig length + sequence, with the 107-byte
+            // signature and pubkey discounted as witness data
+            ScriptType::P2wpkh | ScriptType::P2wsh | ScriptType::P2tr => 32 + 4 + 1 + 107 / 4 + 4,
+            _ => 32 + 4 + 1 + 107 + 4,
+        };
+        let output_size =
+            8 + CompactSize::from_len(&self.script_pubkey).encoded_len() + self.script_pubkey.len();
+        ((output_size + spend_size) as f64 * fee_rate_sat_per_vb) as u64
+    }
+
+    pub fn is_dust(&self, fee_rate_sat_per_vb: f64) -> bool {
+        self.value < self.dust_threshold(fee_rate_sat_per_vb)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.consensus_encode(&mut bytes)
+            .expect("writing to a Vec never fails");
+        bytes
+    }
+
+    pub fn consensus_encode<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
+        w.write_all(&self.value.to_le_bytes())?;
+        let written = self.script_pubkey.consensus_encode(w)?;
+        Ok(8 + written)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        ensure_non_empty(bytes)?;
+        <Self as Decodable>::decode(bytes)
+    }
+}
+
+/// Borrowed counterpart of `TransactionInput`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TransactionInputRef<'a> {
+    pub previous_output: OutPoint,
+    pub script_sig: ScriptRef<'a>,
+    pub sequence: u32,
+}
+
+impl TransactionInputRef<'_> {
+    pub fn to_input(&self) -> TransactionInput {
+        TransactionInput::new(
+            self.previous_output.clone(),
+            self.script_sig.to_script(),
+            self.sequence,
+        )
+    }
+}
+
+/// Borrowed counterpart of `TransactionOutput`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TransactionOutputRef<'a> {
+    pub value: u64,
+    pub script_pubkey: ScriptRef<'a>,
+}
+
+impl TransactionOutputRef<'_> {
+    pub fn to_output(&self) -> TransactionOutput {
+        TransactionOutput::new(self.value, self.script_pubkey.to_script())
+    }
+}
+
+/// A parsed transaction whose scripts and witness items borrow from the
+/// source buffer, see `BitcoinTransaction::from_bytes_borrowed`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TransactionRef<'a> {
+    pub version: u32,
+    pub inputs: Vec<TransactionInputRef<'a>>,
+    pub outputs: Vec<TransactionOutputRef<'a>>,
+    pub lock_time: u32,
+    pub witnesses: Vec<Vec<&'a [u8]>>,
+}
+
+impl TransactionRef<'_> {
+    pub fn to_transaction(&self) -> BitcoinTransaction {
+        let inputs = self.inputs.iter().map(|i| i.to_input()).collect();
+        let outputs = self.outputs.iter().map(|o| o.to_output()).collect();
+        let mut tx = BitcoinTransaction::new(self.version, inputs, outputs, self.lock_time);
+        tx.witnesses = self
+            .witnesses
+            .iter()
+            .map(|stack| stack.iter().map(|item| item.to_vec()).collect())
+            .collect();
+        tx
+    }
+}
+
+/// Whatever `BitcoinTransaction::from_bytes_partial` managed to parse.
+/// Counts are the declared totals; the vectors hold the items read so far.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct PartialTransaction {
+    pub version: Option<u32>,
+    pub segwit: bool,
+    pub input_count: Option<usize>,
+    pub inputs: Vec<TransactionInput>,
+    pub output_count: Option<usize>,
+    pub outputs: Vec<TransactionOutput>,
+    pub witnesses: Vec<Vec<Vec<u8>>>,
+    pub lock_time: Option<u32>,
+    // Why parsing stopped, with the offset; `None` once lock_time is read
+    pub error: Option<BitcoinError>,
+}
+
+impl PartialTransaction {
+    pub fn is_complete(&self) -> bool {
+        self.lock_time.is_some()
+    }
+
+    /// The full transaction, if parsing got all the way through
+    pub fn to_transaction(&self) -> Option<BitcoinTransaction> {
+        let lock_time = self.lock_time?;
+        let mut tx = BitcoinTransaction::new(
+            self.version?,
+            self.inputs.clone(),
+            self.outputs.clone(),
+            lock_time,
+        );
+        tx.witnesses = self.witnesses.clone();
+        Some(tx)
+    }
+
+    // Unlike `read_count`, a count the remaining bytes cannot hold is
+    // accepted: the input is presumed truncated, not malicious, and nothing
+    // is preallocated from it
+    fn read_declared_count(reader: &mut BitcoinReader<'_>) -> Result<usize, BitcoinError> {
+        let mut lookahead = *reader;
+        let count = usize::try_from(lookahead.read_compact_size()?.value)
+            .map_err(|_| BitcoinError::InvalidFormat)?;
+        *reader = lookahead;
+        Ok(count)
+    }
+
+    // Fills in fields one complete item at a time; `reader` only advances
+    // past items that were fully read
+    fn parse(&mut self, reader: &mut BitcoinReader<'_>) -> Result<(), BitcoinError> {
+        self.version = Some(reader.read_u32_le()?);
+
+        // Same marker rules as `BitcoinReader::read_transaction_ref`
+        let mut lookahead = *reader;
+        self.segwit = reader.peek(2) == Some(&[0x00, 0x01]);
+        if self.segwit {
+            lookahead.read_exact(2)?;
+        }
+        let input_count = Self::read_declared_count(&mut lookahead)?;
+        if self.segwit && input_count == 0 {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        *reader = lookahead;
+        self.input_count = Some(input_count);
+
+        for _ in 0..input_count {
+            let mut lookahead = *reader;
+            self.inputs.push(lookahead.read_input()?);
+            *reader = lookahead;
+        }
+
+        let output_count = Self::read_declared_count(reader)?;
+        self.output_count = Some(output_count);
+        for _ in 0..output_count {
+            let mut lookahead = *reader;
+            self.outputs.push(lookahead.read_output()?);
+            *reader = lookahead;
+        }
+
+        if self.segwit {
+            for _ in 0..input_count {
+                let mut lookahead = *reader;
+                let count = Self::read_declared_count(&mut lookahead)?;
+                let mut stack = Vec::new();
+                for _ in 0..count {
+                    stack.push(lookahead.read_var_bytes()?.to_vec());
+                }
+                self.witnesses.push(stack);
+                *reader = lookahead;
+            }
+            if self.witnesses.iter().all(Vec::is_empty) {
+                return Err(BitcoinError::InvalidFormat);
+            }
+        }
+
+        self.lock_time = Some(reader.read_u32_le()?);
+        Ok(())
+    }
+}
+
+pub const SATS_PER_BTC: u64 = 100_000_000;
+
+/// Total supply cap in satoshis
+pub const MAX_MONEY: u64 = 21_000_000 * SATS_PER_BTC;
+
+/// A value in satoshis
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    pub fn from_sat(sat: u64) -> Self {
+        Amount(sat)
+    }
+
+    pub fn to_sat(&self) -> u64 {
+        self.0
+    }
+
+    /// Converts from BTC, rounding to the nearest satoshi. Negative, NaN and
+    /// amounts over the 21 million BTC supply give `AmountOutOfRange`.
+    pub fn from_btc(btc: f64) -> Result<Self, BitcoinError> {
+        let sat = btc * SATS_PER_BTC as f64;
+        // Written so that NaN fails the check
+        if !(sat >= 0.0 && sat <= MAX_MONEY as f64) {
+            return Err(BitcoinError::AmountOutOfRange);
+        }
+        Ok(Amount((sat + 0.5) as u64))
+    }
+
+    pub fn to_btc(&self) -> f64 {
+        self.0 as f64 / SATS_PER_BTC as f64
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+}
+
+pub const SIGHASH_ALL: u32 = 0x01;
+pub const SIGHASH_NONE: u32 = 0x02;
+pub const SIGHASH_SINGLE: u32 = 0x03;
+pub const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+/// Signature hash mode, the base type optionally combined with ANYONECANPAY
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SighashType {
+    All,
+    None,
+    Single,
+    AllPlusAnyoneCanPay,
+    NonePlusAnyoneCanPay,
+    SinglePlusAnyoneCanPay,
+}
+
+impl SighashType {
+    /// Parses a sighash flag. Only the 0x1f base bits and the 0x80
+    /// ANYONECANPAY bit may be set, and the base must be ALL, NONE or SINGLE.
+    pub fn from_u32(n: u32) -> Result<Self, BitcoinError> {
+        if n & !(SIGHASH_ANYONECANPAY | 0x1f) != 0 {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        let anyone_can_pay = n & SIGHASH_ANYONECANPAY != 0;
+        match (n & 0x1f, anyone_can_pay) {
+            (SIGHASH_ALL, false) => Ok(SighashType::All),
+            (SIGHASH_NONE, false) => Ok(SighashType::None),
+            (SIGHASH_SINGLE, false) => Ok(SighashType::Single),
+            (SIGHASH_ALL, true) => Ok(SighashType::AllPlusAnyoneCanPay),
+            (SIGHASH_NONE, true) => Ok(SighashType::NonePlusAnyoneCanPay),
+            (SIGHASH_SINGLE, true) => Ok(SighashType::SinglePlusAnyoneCanPay),
+            _ => Err(BitcoinError::InvalidFormat),
+        }
+    }
+
+    pub fn to_u32(self) -> u32 {
+        match self {
+            SighashType::All => SIGHASH_ALL,
+            SighashType::None => SIGHASH_NONE,
+            SighashType::Single => SIGHASH_SINGLE,
+            SighashType::AllPlusAnyoneCanPay => SIGHASH_ALL | SIGHASH_ANYONECANPAY,
+            SighashType::NonePlusAnyoneCanPay => SIGHASH_NONE | SIGHASH_ANYONECANPAY,
+            SighashType::SinglePlusAnyoneCanPay => SIGHASH_SINGLE | SIGHASH_ANYONECANPAY,
+        }
+    }
+
+    /// The base type with the ANYONECANPAY flag cleared
+    pub fn base(self) -> Self {
+        match self {
+            SighashType::All | SighashType::AllPlusAnyoneCanPay => SighashType::All,
+            SighashType::None | SighashType::NonePlusAnyoneCanPay => SighashType::None,
+            SighashType::Single | SighashType::SinglePlusAnyoneCanPay => SighashType::Single,
+        }
+    }
+
+    pub fn anyone_can_pay(self) -> bool {
+        self.base() != self
+    }
+}
+
+pub const LOCK_TIME_THRESHOLD: u32 = 500_000_000;
+
+fn sha256d(data: &[u8]) -> [u8; 32] {
+    let first = Sha256::digest(data);
+    Sha256::digest(first).into()
+}
+
+// Lets `consensus_encode` stream straight into SHA-256 without building the
+// serialization in memory first
+struct HashWriter(Sha256);
+
+impl HashWriter {
+    fn finish_sha256d(self) -> [u8; 32] {
+        Sha256::digest(self.0.finalize()).into()
+    }
+}
+
+#[cfg(feature = "std")]
+impl io::Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl io::Write for HashWriter {
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.0.update(buf);
+        Ok(())
+    }
+}
+
+/// Bitcoin merkle root over txids in internal byte order. Odd levels pair
+/// the last hash with itself; a single txid is its own root and an empty
+/// list gives all zeros, as in Bitcoin Core.
+pub fn merkle_root(txids: &[[u8; 32]]) -> [u8; 32] {
+    let mut level = txids.to_vec();
+    if level.is_empty() {
+        return [0u8; 32];
+    }
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let mut concat = [0u8; 64];
+                concat[..32].copy_from_slice(&pair[0]);
+                concat[32..].copy_from_slice(pair.get(1).unwrap_or(&pair[0]));
+                sha256d(&concat)
+            })
+            .collect();
+    }
+    level[0]
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct BitcoinTransaction {
+    pub version: u32,
+    pub inputs: Vec<TransactionInput>,
+    pub outputs: Vec<TransactionOutput>,
+    pub lock_time: u32,
+    // One witness stack per input; empty for legacy transactions
+    #[serde(default)]
+    pub witnesses: Vec<Vec<Vec<u8>>>,
+}
+
+/// Version 2 with no inputs or outputs and lock_time 0, like `TransactionBuilder::new`
+impl Default for BitcoinTransaction {
+    fn default() -> Self {
+        BitcoinTransaction::new(2, Vec::new(), Vec::new(), 0)
+    }
+}
+
+/// Deserializes a transaction and rejects it unless `check_sanity` passes,
+/// for use as `#[serde(deserialize_with = "deserialize_validated")]`
+pub fn deserialize_validated<'de, D>(deserializer: D) -> Result<BitcoinTransaction, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let tx = BitcoinTransaction::deserialize(deserializer)?;
+    tx.check_sanity().map_err(serde::de::Error::custom)?;
+    Ok(tx)
+}
+
+impl BitcoinTransaction {
+    pub fn new(
+        version: u32,
+        inputs: Vec<TransactionInput>,
+        outputs: Vec<TransactionOutput>,
+        lock_time: u32,
+    ) -> Self {
+        BitcoinTransaction {
+            version,
+            inputs,
+            outputs,
+            lock_time,
+            witnesses: Vec::new(),
+        }
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = &TransactionInput> {
+        self.inputs.iter()
+    }
+
+    /// Iterates the outputs in order
+    ///
+    /// ```
+    /// use rust_week_3_exercises::{Script, TransactionBuilder};
+    ///
+    /// let tx = TransactionBuilder::new()
+    ///     .add_output(1_000, Script::new(vec![0x51]))
+    ///     .add_output(2_500, Script::new(vec![0x52]))
+    ///     .build();
+    ///
+    /// let total: u64 = tx.outputs().map(|output| output.value).sum();
+    /// assert_eq!(total, 3_500);
+    /// assert_eq!(tx.output_count(), 2);
+    /// ```
+    pub fn outputs(&self) -> impl Iterator<Item = &TransactionOutput> {
+        self.outputs.iter()
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Outpoints spent by this transaction, in input order
+    pub fn prevouts(&self) -> Vec<OutPoint> {
+        self.inputs
+            .iter()
+            .map(|input| input.previous_output.clone())
+            .collect()
+    }
+
+    pub fn spends(&self, outpoint: &OutPoint) -> bool {
+        self.inputs
+            .iter()
+            .any(|input| input.previous_output == *outpoint)
+    }
+
+    // The mutators below keep `witnesses` aligned with `inputs`. Any of them
+    // changes the txid, so signatures made over the old transaction are void.
+
+    /// Removes input `index` together with its witness stack
+    pub fn remove_input(&mut self, index: usize) -> Option<TransactionInput> {
+        if index >= self.inputs.len() {
+            return None;
+        }
+        if index < self.witnesses.len() {
+            self.witnesses.remove(index);
+        }
+        Some(self.inputs.remove(index))
+    }
+
+    /// Appends an input with an empty witness
+    pub fn add_input_mut(&mut self, input: TransactionInput) {
+        if !self.witnesses.is_empty() {
+            self.witnesses.resize(self.inputs.len(), Vec::new());
+            self.witnesses.push(Vec::new());
+        }
+        self.inputs.push(input);
+    }
+
+    pub fn remove_output(&mut self, index: usize) -> Option<TransactionOutput> {
+        (index < self.outputs.len()).then(|| self.outputs.remove(index))
+    }
+
+    pub fn add_output_mut(&mut self, output: TransactionOutput) {
+        self.outputs.push(output);
+    }
+
+    /// Replaces the scriptSig of input `index`
+    pub fn set_input_script(&mut self, index: usize, script: Script) -> Result<(), BitcoinError> {
+        self.check_input_index(index)?;
+        self.inputs[index].script_sig = script;
+        Ok(())
+    }
+
+    /// A copy with only the scriptSig of input `index` replaced
+    pub fn with_input_script(&self, index: usize, script: Script) -> Result<Self, BitcoinError> {
+        let mut tx = self.clone();
+        tx.set_input_script(index, script)?;
+        Ok(tx)
+    }
+
+    /// BIP-69 input order key. Txids compare in display (reversed) byte
+    /// order, which differs from `OutPoint`'s own `Ord`.
+    fn bip69_input_key(input: &TransactionInput) -> ([u8; 32], u32) {
+        let mut txid = input.previous_output.txid.0;
+        txid.reverse();
+        (txid, input.previous_output.vout)
+    }
+
+    fn bip69_output_key(output: &TransactionOutput) -> (u64, &[u8]) {
+        (output.value, &output.script_pubkey.bytes)
+    }
+
+    pub fn is_bip69_sorted(&self) -> bool {
+        self.inputs
+            .windows(2)
+            .all(|w| Self::bip69_input_key(&w[0]) <= Self::bip69_input_key(&w[1]))
+            && self
+                .outputs
+                .windows(2)
+                .all(|w| Self::bip69_output_key(&w[0]) <= Self::bip69_output_key(&w[1]))
+    }
+
+    /// Sorts inputs (with their witnesses) and outputs per BIP-69. The sort is
+    /// stable, so equal keys keep their relative order.
+    pub fn sort_bip69(&mut self) {
+        let mut order: Vec<usize> = (0..self.inputs.len()).collect();
+        order.sort_by_key(|&i| Self::bip69_input_key(&self.inputs[i]));
+
+        let inputs = order.iter().map(|&i| self.inputs[i].clone()).collect();
+        if !self.witnesses.is_empty() {
+            self.witnesses = order
+                .iter()
+                .map(|&i| self.witnesses.get(i).cloned().unwrap_or_default())
+                .collect();
+        }
+        self.inputs = inputs;
+
+        self.outputs
+            .sort_by(|a, b| Self::bip69_output_key(a).cmp(&Self::bip69_output_key(b)));
+    }
+
+    /// True when any input carries witness data, i.e. the transaction
+    /// uses the BIP-144 serialization
+    pub fn is_segwit(&self) -> bool {
+        self.witnesses.iter().any(|witness| !witness.is_empty())
+    }
+
+    /// Drops all witness data, leaving the legacy form. The txid is
+    /// unchanged, and afterwards `to_bytes` is legacy and `wtxid == txid`.
+    pub fn strip_witnesses(&mut self) {
+        self.witnesses = Vec::new();
+    }
+
+    /// Where each field lands in `to_bytes()`, without serializing
+    pub fn field_offsets(&self) -> TransactionLayout {
+        let mut position = 0;
+        let mut next = |len: usize| {
+            position += len;
+            position - len..position
+        };
+
+        let version = next(4);
+        let segwit = self.is_segwit();
+        let marker = segwit.then(|| next(2));
+        let input_count = next(CompactSize::from_len(&self.inputs).encoded_len());
+        let inputs = self.inputs.iter().map(|i| next(i.wire_size())).collect();
+        let output_count = next(CompactSize::from_len(&self.outputs).encoded_len());
+        let outputs = self.outputs.iter().map(|o| next(o.wire_size())).collect();
+        let mut witnesses = Vec::new();
+        if segwit {
+            for index in 0..self.inputs.len() {
+                let stack = self.witness_for(index).unwrap_or_default();
+                let len = CompactSize::from_len(stack).encoded_len()
+                    + stack
+                        .iter()
+                        .map(|item| CompactSize::from_len(item).encoded_len() + item.len())
+                        .sum::<usize>();
+                witnesses.push(next(len));
+            }
+        }
+        let lock_time = next(4);
+
+        TransactionLayout {
+            version,
+            marker,
+            input_count,
+            inputs,
+            output_count,
+            outputs,
+            witnesses,
+            lock_time,
+        }
+    }
+
+    /// Every field that differs from `other`, in serialization order.
+    /// Inputs and outputs are compared by index up to the shorter list; a
+    /// count mismatch is reported, not the extra items. Missing witness
+    /// stacks compare equal to empty ones.
+    pub fn diff(&self, other: &Self) -> Vec<FieldDiff> {
+        let mut diffs = Vec::new();
+        if self.version != other.version {
+            diffs.push(FieldDiff::Version {
+                left: self.version,
+                right: other.version,
+            });
+        }
+
+        if self.inputs.len() != other.inputs.len() {
+            diffs.push(FieldDiff::InputCount {
+                left: self.inputs.len(),
+                right: other.inputs.len(),
+            });
+        }
+        for (index, (a, b)) in self.inputs.iter().zip(&other.inputs).enumerate() {
+            if a.previous_output != b.previous_output {
+                diffs.push(FieldDiff::PreviousOutput {
+                    index,
+                    left: a.previous_output.clone(),
+                    right: b.previous_output.clone(),
+                });
+            }
+            if a.script_sig != b.script_sig {
+                diffs.push(FieldDiff::ScriptSig {
+                    index,
+                    left: a.script_sig.clone(),
+                    right: b.script_sig.clone(),
+                });
+            }
+            if a.sequence != b.sequence {
+                diffs.push(FieldDiff::Sequence {
+                    index,
+                    left: a.sequence,
+                    right: b.sequence,
+                });
+            }
+        }
+
+        if self.outputs.len() != other.outputs.len() {
+            diffs.push(FieldDiff::OutputCount {
+                left: self.outputs.len(),
+                right: other.outputs.len(),
+            });
+        }
+        for (index, (a, b)) in self.outputs.iter().zip(&other.outputs).enumerate() {
+            if a.value != b.value {
+                diffs.push(FieldDiff::Value {
+                    index,
+                    left: a.value,
+                    right: b.value,
+                });
+            }
+            if a.script_pubkey != b.script_pubkey {
+                diffs.push(FieldDiff::ScriptPubKey {
+                    index,
+                    left: a.script_pubkey.clone(),
+                    right: b.script_pubkey.clone(),
+                });
+            }
+        }
+
+        // Witnesses are serialized after the outputs
+        for index in 0..self.inputs.len().min(other.inputs.len()) {
+            let (a, b) = (self.witness_for(index), other.witness_for(index));
+            if a != b {
+                diffs.push(FieldDiff::Witness {
+                    index,
+                    left: a.unwrap_or_default().to_vec(),
+                    right: b.unwrap_or_default().to_vec(),
+                });
+            }
+        }
+
+        if self.lock_time != other.lock_time {
+            diffs.push(FieldDiff::LockTime {
+                left: self.lock_time,
+                right: other.lock_time,
+            });
+        }
+        diffs
+    }
+
+    /// Witness stack of input `index`; empty for inputs without witness data.
+    /// `None` if there is no such input.
+    pub fn witness_for(&self, index: usize) -> Option<&[Vec<u8>]> {
+        if index >= self.inputs.len() {
+            return None;
+        }
+        Some(self.witnesses.get(index).map_or(&[], Vec::as_slice))
+    }
+
+    pub fn witness_item_count(&self, index: usize) -> usize {
+        self.witness_for(index).map_or(0, <[_]>::len)
+    }
+
+    /// Last witness item of input `index`: the witness script for P2WSH or
+    /// the control block for a P2TR script-path spend
+    pub fn witness_last(&self, index: usize) -> Option<&[u8]> {
+        self.witness_for(index)?.last().map(Vec::as_slice)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.serialize(self.is_segwit())
+    }
+
+    /// Pre-segwit serialization without marker, flag or witnesses, even when
+    /// witnesses are present. This is what the txid commits to.
+    pub fn to_bytes_legacy(&self) -> Vec<u8> {
+        self.serialize(false)
+    }
+
+    /// Writes the consensus serialization, including witnesses for segwit
+    /// transactions, and returns the number of bytes written
+    pub fn consensus_encode<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
+        self.encode_with(w, self.is_segwit())
+    }
+
+    /// Length of `to_bytes()`, computed without serializing
+    pub fn serialized_len(&self) -> usize {
+        self.len_with(self.is_segwit())
+    }
+
+    fn len_with(&self, include_witness: bool) -> usize {
+        // CompactSize length prefix plus the prefixed bytes
+        let var_len = |n: usize| CompactSize::from_usize(n).encoded_len() + n;
+
+        let mut len = 4 + CompactSize::from_len(&self.inputs).encoded_len();
+        for input in &self.inputs {
+            len += 36 + var_len(input.script_sig.len()) + 4;
+        }
+        len += CompactSize::from_len(&self.outputs).encoded_len();
+        for output in &self.outputs {
+            len += 8 + var_len(output.script_pubkey.len());
+        }
+        if include_witness {
+            len += 2;
+            for i in 0..self.inputs.len() {
+                len += self.witness_len(i);
+            }
+        }
+        len + 4
+    }
+
+    // Serialized length of input `index`'s witness stack, item count included
+    fn witness_len(&self, index: usize) -> usize {
+        let witness = self.witnesses.get(index).map(Vec::as_slice).unwrap_or(&[]);
+        CompactSize::from_len(witness).encoded_len()
+            + witness
+                .iter()
+                .map(|item| CompactSize::from_len(item).encoded_len() + item.len())
+                .sum::<usize>()
+    }
+
+    // sha256d of `serialize(include_witness)`, without the allocation
+    fn hash_serialization(&self, include_witness: bool) -> [u8; 32] {
+        let mut writer = HashWriter(Sha256::new());
+        self.encode_with(&mut writer, include_witness)
+            .expect("hashing never fails");
+        writer.finish_sha256d()
+    }
+
+    fn serialize(&self, include_witness: bool) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len_with(include_witness));
+        self.encode_with(&mut bytes, include_witness)
+            .expect("writing to a Vec never fails");
+        bytes
+    }
+
+    fn encode_with<W: io::Write>(&self, w: &mut W, include_witness: bool) -> io::Result<usize> {
+        let mut written = 0;
+
+        // Version (4 bytes LE)
+        w.write_all(&self.version.to_le_bytes())?;
+        written += 4;
+
+        // Segwit marker and flag
+        if include_witness {
+            w.write_all(&[0x00, 0x01])?;
+            written += 2;
+        }
+
+        // CompactSize (number of inputs), then each input
+        written += CompactSize::from_len(&self.inputs).consensus_encode(w)?;
+        for input in &self.inputs {
+            written += input.consensus_encode(w)?;
+        }
+
+        // CompactSize (number of outputs), then each output
+        written += CompactSize::from_len(&self.outputs).consensus_encode(w)?;
+        for output in &self.outputs {
+            written += output.consensus_encode(w)?;
+        }
+
+        // Witness stack for each input, empty when missing
+        if include_witness {
+            for i in 0..self.inputs.len() {
+                let witness = self.witnesses.get(i).map(Vec::as_slice).unwrap_or(&[]);
+                written += CompactSize::from_len(witness).consensus_encode(w)?;
+                for item in witness {
+                    written += CompactSize::from_len(item).consensus_encode(w)?;
+                    w.write_all(item)?;
+                    written += item.len();
+                }
+            }
+        }
+
+        // Lock time (4 bytes LE)
+        w.write_all(&self.lock_time.to_le_bytes())?;
+        written += 4;
+
+        Ok(written)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        ensure_non_empty(bytes)?;
+        <Self as Decodable>::decode(bytes)
+    }
+
+    /// Decodes into `out`, reusing its allocations, and returns the bytes
+    /// consumed. For loops that decode many transactions.
+    pub fn from_bytes_into(
+        bytes: &[u8],
+        out: &mut BitcoinTransaction,
+    ) -> Result<usize, BitcoinError> {
+        ensure_non_empty(bytes)?;
+        let mut reader = BitcoinReader::new(bytes);
+        reader
+            .read_transaction_into(out)
+            .map_err(|e| e.at(reader.position()))?;
+        Ok(reader.position())
+    }
+
+    /// `from_bytes` with custom anti-DoS limits in place of the defaults
+    pub fn from_bytes_with_limits(
+        bytes: &[u8],
+        limits: &Limits,
+    ) -> Result<(Self, usize), BitcoinError> {
+        ensure_non_empty(bytes)?;
+        let mut reader = BitcoinReader::new(bytes).with_limits(*limits);
+        let tx = reader
+            .read_transaction()
+            .map_err(|e| e.at(reader.position()))?;
+        Ok((tx, reader.position()))
+    }
+
+    /// `from_bytes` with the segwit marker detection overridden by `mode`
+    pub fn from_bytes_with_mode(
+        bytes: &[u8],
+        mode: SerializeMode,
+    ) -> Result<(Self, usize), BitcoinError> {
+        ensure_non_empty(bytes)?;
+        let mut reader = BitcoinReader::new(bytes);
+        let tx = reader
+            .read_transaction_ref_as(mode)
+            .map_err(|e| e.at(reader.position()))?;
+        Ok((tx.to_transaction(), reader.position()))
+    }
+
+    /// Pre-segwit signature hash for `input_index`. `script_code` is placed in
+    /// the signed input as-is, so strip any OP_CODESEPARATOR beforehand.
+    /// SIGHASH_SINGLE without a matching output returns the consensus
+    /// "one" hash (0x01 followed by zeros), as Bitcoin Core does.
+    pub fn legacy_sighash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        sighash_type: SighashType,
+    ) -> Result<[u8; 32], BitcoinError> {
+        self.check_input_index(input_index)?;
+
+        let base_type = sighash_type.base();
+        if base_type == SighashType::Single && input_index >= self.outputs.len() {
+            let mut one = [0u8; 32];
+            one[0] = 0x01;
+            return Ok(one);
+        }
+
+        let mut tx = self.clone();
+        tx.witnesses.clear();
+        for (i, input) in tx.inputs.iter_mut().enumerate() {
+            if i == input_index {
+                input.script_sig = script_code.clone();
+            } else {
+                input.script_sig = Script::new(Vec::new());
+                // NONE and SINGLE let other inputs update their sequence
+                if base_type != SighashType::All {
+                    input.sequence = 0;
+                }
+            }
+        }
+
+        match base_type {
+            SighashType::None => tx.outputs.clear(),
+            SighashType::Single => {
+                tx.outputs.truncate(input_index + 1);
+                for output in &mut tx.outputs[..input_index] {
+                    *output = TransactionOutput::new(u64::MAX, Script::new(Vec::new()));
+                }
+            }
+            _ => {}
+        }
+
+        if sighash_type.anyone_can_pay() {
+            tx.inputs = vec![tx.inputs.swap_remove(input_index)];
+        }
+
+        let mut preimage = tx.serialize(false);
+        preimage.extend_from_slice(&sighash_type.to_u32().to_le_bytes());
+        Ok(sha256d(&preimage))
+    }
+
+    /// BIP-143 signature hash for spending a segwit v0 output worth `amount`
+    /// satoshis. `script_code` is given without its length prefix.
+    pub fn segwit_sighash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        amount: u64,
+        sighash_type: SighashType,
+    ) -> Result<[u8; 32], BitcoinError> {
+        self.check_input_index(input_index)?;
+
+        let base_type = sighash_type.base();
+        let anyone_can_pay = sighash_type.anyone_can_pay();
+        let single_or_none = base_type != SighashType::All;
+
+        let mut hash_prevouts = [0u8; 32];
+        if !anyone_can_pay {
+            let mut prevouts = Vec::with_capacity(36 * self.inputs.len());
+            for input in &self.inputs {
+                prevouts.extend_from_slice(&input.previous_output.to_bytes());
+            }
+            hash_prevouts = sha256d(&prevouts);
+        }
+
+        let mut hash_sequence = [0u8; 32];
+        if !anyone_can_pay && !single_or_none {
+            let mut sequences = Vec::with_capacity(4 * self.inputs.len());
+            for input in &self.inputs {
+                sequences.extend_from_slice(&input.sequence.to_le_bytes());
+            }
+            hash_sequence = sha256d(&sequences);
+        }
+
+        let mut hash_outputs = [0u8; 32];
+        if !single_or_none {
+            let mut outputs = Vec::new();
+            for output in &self.outputs {
+                outputs.extend_from_slice(&output.to_bytes());
+            }
+            hash_outputs = sha256d(&outputs);
+        } else if base_type == SighashType::Single && input_index < self.outputs.len() {
+            hash_outputs = sha256d(&self.outputs[input_index].to_bytes());
+        }
+
+        let input = &self.inputs[input_index];
+        let mut preimage = Vec::new();
+        preimage.extend_from_slice(&self.version.to_le_bytes());
+        preimage.extend_from_slice(&hash_prevouts);
+        preimage.extend_from_slice(&hash_sequence);
+        preimage.extend_from_slice(&input.previous_output.to_bytes());
+        preimage.extend_from_slice(&script_code.to_bytes());
+        preimage.extend_from_slice(&amount.to_le_bytes());
+        preimage.extend_from_slice(&input.sequence.to_le_bytes());
+        preimage.extend_from_slice(&hash_outputs);
+        preimage.extend_from_slice(&self.lock_time.to_le_bytes());
+        preimage.extend_from_slice(&sighash_type.to_u32().to_le_bytes());
+        Ok(sha256d(&preimage))
+    }
+
+    /// Fee in satoshis given the value of each spent output, in input order
+    pub fn fee(&self, input_amounts: &[u64]) -> Result<u64, BitcoinError> {
+        if input_amounts.len() != self.inputs.len() {
+            return Err(BitcoinError::AmountCountMismatch {
+                expected: self.inputs.len(),
+                actual: input_amounts.len(),
+            });
+        }
+
+        let mut total_in: u64 = 0;
+        for amount in input_amounts {
+            total_in = total_in
+                .checked_add(*amount)
+                .ok_or(BitcoinError::ValueOverflow)?;
+        }
+        total_in
+            .checked_sub(self.total_output_value()?)
+            .ok_or(BitcoinError::NegativeFee)
+    }
+
+    /// Sum of all output values in satoshis; `ValueOverflow` if it exceeds u64
+    pub fn total_output_value(&self) -> Result<u64, BitcoinError> {
+        self.outputs.iter().try_fold(0u64, |total, output| {
+            total
+                .checked_add(output.value)
+                .ok_or(BitcoinError::ValueOverflow)
+        })
+    }
+
+    fn check_input_index(&self, index: usize) -> Result<(), BitcoinError> {
+        if index >= self.inputs.len() {
+            return Err(BitcoinError::InputIndexOutOfRange {
+                index,
+                count: self.inputs.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Flags version 0 and versions above `MAX_STANDARD_TX_VERSION`. Parsing
+    /// never calls this, since such transactions are still valid.
+    pub fn validate_version(&self) -> Result<(), BitcoinError> {
+        if !Version(self.version).is_standard() {
+            return Err(BitcoinError::NonStandardVersion(self.version));
+        }
+        Ok(())
+    }
+
+    /// Context-free structural checks modelled on Bitcoin Core's
+    /// `CheckTransaction`. Output values must each be at most `MAX_MONEY`
+    /// (`AmountOutOfRange`) and so must their sum (`ValueOverflow`).
+    pub fn check_sanity(&self) -> Result<(), BitcoinError> {
+        if self.inputs.is_empty() {
+            return Err(BitcoinError::NoInputs);
+        }
+        if self.outputs.is_empty() {
+            return Err(BitcoinError::NoOutputs);
+        }
+
+        let mut total: u64 = 0;
+        for output in &self.outputs {
+            if output.value > MAX_MONEY {
+                return Err(BitcoinError::AmountOutOfRange);
+            }
+            total += output.value;
+            if total > MAX_MONEY {
+                return Err(BitcoinError::ValueOverflow);
+            }
+        }
+
+        if let Some(index) = self.first_duplicate_input() {
+            return Err(BitcoinError::DuplicateInput { index });
+        }
+
+        if self.is_coinbase() {
+            let len = self.inputs[0].script_sig.len();
+            if !(2..=100).contains(&len) {
+                return Err(BitcoinError::CoinbaseScriptLength(len));
+            }
+        } else if let Some(index) = self
+            .inputs
+            .iter()
+            .position(|input| input.previous_output.is_null())
+        {
+            return Err(BitcoinError::NullPrevout { index });
+        }
+        Ok(())
+    }
+
+    /// Whether two inputs spend the same outpoint, which consensus forbids
+    pub fn has_duplicate_inputs(&self) -> bool {
+        self.first_duplicate_input().is_some()
+    }
+
+    // Index of the first input whose outpoint an earlier input already spends.
+    // A `BTreeSet` rather than `HashSet` keeps this available without `std`.
+    fn first_duplicate_input(&self) -> Option<usize> {
+        let mut spent = BTreeSet::new();
+        self.inputs
+            .iter()
+            .position(|input| !spent.insert(&input.previous_output))
+    }
+
+    /// A single input spending the null outpoint (zero txid, vout 0xFFFFFFFF)
+    pub fn is_coinbase(&self) -> bool {
+        match self.inputs.as_slice() {
+            [input] => input.previous_output.is_null(),
+            _ => false,
+        }
+    }
+
+    /// Block height committed at the start of a coinbase scriptSig (BIP-34).
+    /// `None` for non-coinbase transactions or scriptSigs that do not begin
+    /// with a non-negative push of up to 4 bytes.
+    pub fn coinbase_height(&self) -> Option<u32> {
+        if !self.is_coinbase() {
+            return None;
+        }
+        match self.inputs[0].script_sig.instructions().next()?.ok()? {
+            Instruction::Push { data, .. } if data.len() <= 4 => {
+                if data.last().is_some_and(|last| last & 0x80 != 0) {
+                    return None;
+                }
+                let mut height = [0u8; 4];
+                height[..data.len()].copy_from_slice(data);
+                Some(u32::from_le_bytes(height))
+            }
+            Instruction::Op(opcode @ opcodes::OP_1..=opcodes::OP_16) => {
+                Some((opcode - opcodes::OP_1 + 1) as u32)
+            }
+            _ => None,
+        }
+    }
+
+    /// BIP-125 opt-in replace-by-fee: any input with a sequence below
+    /// 0xFFFFFFFE signals replaceability
+    pub fn is_rbf_signaling(&self) -> bool {
+        self.inputs.iter().any(|input| input.sequence < 0xFFFF_FFFE)
+    }
+
+    /// Lock times below 500,000,000 are block heights, the rest unix timestamps
+    pub fn lock_time_is_height(&self) -> bool {
+        self.lock_time < LOCK_TIME_THRESHOLD
+    }
+
+    pub fn lock_time_as_height(&self) -> Option<u32> {
+        self.lock_time_is_height().then_some(self.lock_time)
+    }
+
+    pub fn lock_time_as_timestamp(&self) -> Option<u32> {
+        (!self.lock_time_is_height()).then_some(self.lock_time)
+    }
+
+    /// True when the lock time cannot delay inclusion: it is zero or every
+    /// input has a final (0xFFFFFFFF) sequence
+    pub fn is_final(&self) -> bool {
+        self.lock_time == 0
+            || self
+                .inputs
+                .iter()
+                .all(|input| input.sequence == 0xFFFF_FFFF)
+    }
+
+    /// Full serialized length in bytes, including witness data
+    pub fn size(&self) -> usize {
+        self.serialized_len()
+    }
+
+    /// Serialized length without marker, flag and witnesses
+    pub fn base_size(&self) -> usize {
+        self.len_with(false)
+    }
+
+    /// Weight units per BIP-141
+    pub fn weight(&self) -> usize {
+        self.base_size() * 3 + self.size()
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.weight().div_ceil(4)
+    }
+
+    /// Where `weight()` comes from. Inputs are charged their own bytes at
+    /// four units each plus their witness stack at one unit per byte.
+    pub fn weight_breakdown(&self) -> WeightBreakdown {
+        let segwit = self.is_segwit();
+        let base = self.base_size() * 4;
+        let inputs = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let witness = if segwit { self.witness_len(i) } else { 0 };
+                input.wire_size() * 4 + witness
+            })
+            .collect();
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|output| output.wire_size() * 4)
+            .collect();
+        WeightBreakdown {
+            base,
+            witness: self.size() - self.base_size(),
+            inputs,
+            outputs,
+        }
+    }
+
+    /// Virtual size once signed, given the type of output each input
+    /// spends. Current scriptSigs and witnesses are ignored in favour of the
+    /// `ScriptType::input_weight` projections. OP_RETURN and non-standard
+    /// types have no estimate and give `InvalidFormat`.
+    pub fn estimated_vsize(
+        &self,
+        input_script_types: &[ScriptType],
+    ) -> Result<usize, BitcoinError> {
+        if input_script_types.len() != self.inputs.len() {
+            return Err(BitcoinError::ScriptTypeCountMismatch {
+                expected: self.inputs.len(),
+                actual: input_script_types.len(),
+            });
+        }
+
+        let mut weight = 0;
+        for script_type in input_script_types {
+            match script_type.input_weight() {
+                0 => return Err(BitcoinError::InvalidFormat),
+                input_weight => weight += input_weight,
+            }
+        }
+
+        // Version, counts, outputs and lock time, at 4 WU per byte
+        let rest = 4
+            + CompactSize::from_len(&self.inputs).encoded_len()
+            + CompactSize::from_len(&self.outputs).encoded_len()
+            + self.outputs.iter().map(|o| o.wire_size()).sum::<usize>()
+            + 4;
+        weight += rest * 4;
+
+        // Marker and flag, plus an empty stack count for each legacy input
+        let legacy_inputs = input_script_types
+            .iter()
+            .filter(|t| **t == ScriptType::P2pkh)
+            .count();
+        if legacy_inputs < input_script_types.len() {
+            weight += 2 + legacy_inputs;
+        }
+
+        Ok(weight.div_ceil(4))
+    }
+
+    /// Parses without copying scripts or witness items out of `bytes`
+    pub fn from_bytes_borrowed(bytes: &[u8]) -> Result<(TransactionRef<'_>, usize), BitcoinError> {
+        ensure_non_empty(bytes)?;
+        let mut reader = BitcoinReader::new(bytes);
+        let tx = reader
+            .read_transaction_ref()
+            .map_err(|e| e.at(reader.position()))?;
+        Ok((tx, reader.position()))
+    }
+
+    /// Parses a complete serialized transaction, rejecting trailing bytes
+    pub fn parse_exact(bytes: &[u8]) -> Result<Self, BitcoinError> {
+        let (tx, consumed) = BitcoinTransaction::from_bytes(bytes)?;
+        if consumed != bytes.len() {
+            return Err(BitcoinError::InvalidFormat.at(consumed));
+        }
+        Ok(tx)
+    }
+
+    /// Parses as far as the bytes allow instead of failing outright,
+    /// returning the fields read so far and the offset reached. Validation
+    /// errors stop parsing the same way truncation does.
+    pub fn from_bytes_partial(bytes: &[u8]) -> (PartialTransaction, usize) {
+        let mut partial = PartialTransaction::default();
+        let mut reader = BitcoinReader::new(bytes);
+        let result = ensure_non_empty(bytes).and_then(|()| partial.parse(&mut reader));
+        partial.error = result.err().map(|e| e.at(reader.position()));
+        (partial, reader.position())
+    }
+
+    /// Decodes exactly `count` back-to-back transactions, e.g. a block's
+    /// transaction list, rejecting trailing bytes
+    pub fn decode_all(bytes: &[u8], count: usize) -> Result<Vec<Self>, BitcoinError> {
+        let mut reader = BitcoinReader::new(bytes);
+        // Don't trust `count` for the allocation size
+        let mut txs = Vec::with_capacity(count.min(bytes.len() / Self::MIN_ENCODED_LEN));
+        for _ in 0..count {
+            let tx = reader
+                .read_transaction()
+                .map_err(|e| e.at(reader.position()))?;
+            txs.push(tx);
+        }
+        if reader.remaining() != 0 {
+            return Err(BitcoinError::InvalidFormat.at(reader.position()));
+        }
+        Ok(txs)
+    }
+
+    /// Parses a transaction from its hex encoding, rejecting trailing bytes
+    pub fn from_hex(s: &str) -> Result<Self, BitcoinError> {
+        let bytes = hex::decode(s).map_err(|_| BitcoinError::InvalidFormat)?;
+        BitcoinTransaction::parse_exact(&bytes)
+    }
+
+    /// Reads a transaction dump that is either raw bytes or hex text
+    /// (surrounding whitespace allowed), rejecting trailing bytes
+    #[cfg(feature = "std")]
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, BitcoinError> {
+        let contents = std::fs::read(path).map_err(|e| BitcoinError::Io(e.to_string()))?;
+        let trimmed = contents.trim_ascii();
+        if !trimmed.is_empty() && trimmed.iter().all(u8::is_ascii_hexdigit) {
+            let bytes = hex::decode(trimmed).map_err(|_| BitcoinError::InvalidFormat)?;
+            return BitcoinTransaction::parse_exact(&bytes);
+        }
+        BitcoinTransaction::parse_exact(&contents)
+    }
+
+    /// Reads exactly one transaction from an async stream, leaving any
+    /// following bytes unread. The bytes are framed field by field as they
+    /// arrive and then validated by `parse_exact`. End of stream mid-way is
+    /// `InsufficientBytes`; other read failures are `Io`.
+    #[cfg(feature = "tokio")]
+    pub async fn from_async_reader<R>(r: &mut R) -> Result<Self, BitcoinError>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        let mut framer = AsyncFramer {
+            reader: r,
+            buf: Vec::new(),
+        };
+        framer.take(4).await?;
+
+        // Same marker rules as `BitcoinReader::read_transaction_ref`; with
+        // zero inputs the byte after the 0x00 begins the output count
+        let start = framer.buf.len();
+        framer.take(1).await?;
+        let (segwit, input_count, output_start) = if framer.buf[start] == 0x00 {
+            let next = framer.buf.len();
+            framer.take(1).await?;
+            if framer.buf[next] == 0x01 {
+                (true, framer.compact_size().await?, None)
+            } else {
+                (false, 0, Some(next))
+            }
+        } else {
+            (false, framer.finish_compact_size(start).await?, None)
+        };
+
+        for _ in 0..input_count {
+            framer.take(36).await?;
+            framer.script().await?;
+            framer.take(4).await?;
+        }
+
+        let output_count = match output_start {
+            Some(start) => framer.finish_compact_size(start).await?,
+            None => framer.compact_size().await?,
+        };
+        for _ in 0..output_count {
+            framer.take(8).await?;
+            framer.script().await?;
+        }
+
+        if segwit {
+            for _ in 0..input_count {
+                for _ in 0..framer.compact_size().await? {
+                    let len = framer.compact_size().await?;
+                    framer.take(len).await?;
+                }
+            }
+        }
+
+        framer.take(4).await?;
+        BitcoinTransaction::parse_exact(&framer.buf)
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    /// Storage framing: `FRAME_MAGIC`, `FRAME_VERSION`, the payload length
+    /// as a u32 LE, then the consensus bytes. Not a consensus format.
+    /// Panics if the serialization exceeds `u32::MAX` bytes.
+    pub fn to_framed_bytes(&self) -> Vec<u8> {
+        let payload = self.to_bytes();
+        let len = u32::try_from(payload.len()).expect("framed payload exceeds u32::MAX bytes");
+        let mut framed = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+        framed.extend_from_slice(&FRAME_MAGIC);
+        framed.push(FRAME_VERSION);
+        framed.extend_from_slice(&len.to_le_bytes());
+        framed.extend_from_slice(&payload);
+        framed
+    }
+
+    /// Parses one frame written by `to_framed_bytes`, returning the bytes
+    /// consumed. Bad magic, an unknown version, or a payload that does not
+    /// decode to exactly the framed length give `InvalidFormat`. Errors
+    /// inside the payload carry offsets from the start of the frame. The
+    /// payload is parsed without `Limits`, so anything `to_framed_bytes`
+    /// writes reads back; the framed length already bounds every allocation.
+    pub fn from_framed_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        ensure_non_empty(bytes)?;
+        let mut reader = BitcoinReader::new(bytes);
+        if reader.read_exact(4)? != FRAME_MAGIC || reader.read_u8()? != FRAME_VERSION {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        let len = reader.read_u32_le()? as usize;
+        let payload = reader.read_exact(len)?;
+        let mut payload_reader = BitcoinReader::new(payload).with_limits(Limits {
+            max_inputs: usize::MAX,
+            max_outputs: usize::MAX,
+            max_script_len: usize::MAX,
+            max_tx_size: usize::MAX,
+        });
+        let tx = payload_reader
+            .read_transaction()
+            .map_err(|e| e.at(payload_reader.position()).shifted(FRAME_HEADER_LEN))?;
+        let consumed = payload_reader.position();
+        if consumed != payload.len() {
+            return Err(BitcoinError::InvalidFormat.at(FRAME_HEADER_LEN + consumed));
+        }
+        Ok((tx, reader.position()))
+    }
+
+    /// Double SHA-256 of the serialized transaction without witness data,
+    /// in internal byte order
+    pub fn txid(&self) -> [u8; 32] {
+        self.hash_serialization(false)
+    }
+
+    /// Txid as shown by block explorers and RPC (reversed byte order)
+    pub fn txid_hex(&self) -> String {
+        Txid(self.txid()).to_display_hex()
+    }
+
+    /// True when both transactions have the same non-witness serialization,
+    /// i.e. the same txid, without hashing either of them
+    pub fn same_txid(&self, other: &Self) -> bool {
+        self.serialize(false) == other.serialize(false)
+    }
+
+    /// Double SHA-256 of the full serialization including marker, flag and
+    /// witnesses, in internal byte order. Equals `txid` for legacy transactions.
+    pub fn wtxid(&self) -> [u8; 32] {
+        self.hash_serialization(self.is_segwit())
+    }
+
+    /// Wtxid as shown by block explorers and RPC (reversed byte order)
+    pub fn wtxid_hex(&self) -> String {
+        Txid(self.wtxid()).to_display_hex()
+    }
+
+    /// JSON in the shape of Bitcoin Core's verbose `getrawtransaction`.
+    /// Txids are in display order and output values in BTC.
+    pub fn to_json_verbose(&self) -> serde_json::Value {
+        let vin: Vec<serde_json::Value> = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let mut entry = serde_json::json!({
+                    "txid": Txid(input.previous_output.txid.0).to_display_hex(),
+                    "vout": input.previous_output.vout,
+                    "scriptSig": { "hex": hex::encode(&input.script_sig.bytes) },
+                    "sequence": input.sequence,
+                });
+                if let Some(stack) = self.witnesses.get(i).filter(|s| !s.is_empty()) {
+                    let items: Vec<String> = stack.iter().map(hex::encode).collect();
+                    entry["txinwitness"] = items.into();
+                }
+                entry
+            })
+            .collect();
+
+        let vout: Vec<serde_json::Value> = self
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(n, output)| {
+                serde_json::json!({
+                    "value": output.amount().to_btc(),
+                    "n": n,
+                    "scriptPubKey": { "hex": hex::encode(&output.script_pubkey.bytes) },
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "txid": self.txid_hex(),
+            "hash": self.wtxid_hex(),
+            "version": self.version,
+            "size": self.size(),
+            "vsize": self.vsize(),
+            "weight": self.weight(),
+            "locktime": self.lock_time,
+            "vin": vin,
+            "vout": vout,
+        })
+    }
+}
+
+/// Weight split from `BitcoinTransaction::weight_breakdown`. `base` and
+/// `witness` sum to the total; `inputs` and `outputs` itemize the same total
+/// minus `overhead()`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WeightBreakdown {
+    /// Non-witness bytes at four units each
+    pub base: usize,
+    /// Marker, flag and witness stacks at one unit each
+    pub witness: usize,
+    pub inputs: Vec<usize>,
+    pub outputs: Vec<usize>,
+}
+
+impl WeightBreakdown {
+    pub fn total(&self) -> usize {
+        self.base + self.witness
+    }
+
+    /// Weight of the version, counts, lock time and segwit marker
+    pub fn overhead(&self) -> usize {
+        self.total() - self.inputs.iter().sum::<usize>() - self.outputs.iter().sum::<usize>()
+    }
+}
+
+/// Byte ranges of each field within `BitcoinTransaction::to_bytes()`, from
+/// `BitcoinTransaction::field_offsets`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TransactionLayout {
+    pub version: Range<usize>,
+    /// Segwit marker and flag, present only for segwit serializations
+    pub marker: Option<Range<usize>>,
+    pub input_count: Range<usize>,
+    pub inputs: Vec<Range<usize>>,
+    pub output_count: Range<usize>,
+    pub outputs: Vec<Range<usize>>,
+    /// One stack per input, each with its item count; empty for legacy
+    pub witnesses: Vec<Range<usize>>,
+    pub lock_time: Range<usize>,
+}
+
+/// One field that differs between two transactions, from
+/// `BitcoinTransaction::diff`: `left` is from `self`, `right` from `other`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum FieldDiff {
+    Version {
+        left: u32,
+        right: u32,
+    },
+    InputCount {
+        left: usize,
+        right: usize,
+    },
+    PreviousOutput {
+        index: usize,
+        left: OutPoint,
+        right: OutPoint,
+    },
+    ScriptSig {
+        index: usize,
+        left: Script,
+        right: Script,
+    },
+    Sequence {
+        index: usize,
+        left: u32,
+        right: u32,
+    },
+    Witness {
+        index: usize,
+        left: Vec<Vec<u8>>,
+        right: Vec<Vec<u8>>,
+    },
+    OutputCount {
+        left: usize,
+        right: usize,
+    },
+    Value {
+        index: usize,
+        left: u64,
+        right: u64,
+    },
+    ScriptPubKey {
+        index: usize,
+        left: Script,
+        right: Script,
+    },
+    LockTime {
+        left: u32,
+        right: u32,
+    },
+}
+
+impl FieldDiff {
+    /// Location of the field, e.g. `outputs[1].value`
+    pub fn path(&self) -> String {
+        match self {
+            FieldDiff::Version { .. } => "version".to_string(),
+            FieldDiff::InputCount { .. } => "inputs.len".to_string(),
+            FieldDiff::PreviousOutput { index, .. } => format!("inputs[{}].previous_output", index),
+            FieldDiff::ScriptSig { index, .. } => format!("inputs[{}].script_sig", index),
+            FieldDiff::Sequence { index, .. } => format!("inputs[{}].sequence", index),
+            FieldDiff::Witness { index, .. } => format!("witnesses[{}]", index),
+            FieldDiff::OutputCount { .. } => "outputs.len".to_string(),
+            FieldDiff::Value { index, .. } => format!("outputs[{}].value", index),
+            FieldDiff::ScriptPubKey { index, .. } => format!("outputs[{}].script_pubkey", index),
+            FieldDiff::LockTime { .. } => "lock_time".to_string(),
+        }
+    }
+}
+
+/// `path: left != right`, with scripts and witness items in hex
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stack_hex = |stack: &[Vec<u8>]| {
+            let items: Vec<String> = stack.iter().map(hex::encode).collect();
+            format!("[{}]", items.join(" "))
+        };
+        let outpoint = |o: &OutPoint| format!("{}:{}", o.txid.to_display_hex(), o.vout);
+        let (left, right) = match self {
+            FieldDiff::Version { left, right }
+            | FieldDiff::LockTime { left, right }
+            | FieldDiff::Sequence { left, right, .. } => (left.to_string(), right.to_string()),
+            FieldDiff::InputCount { left, right } | FieldDiff::OutputCount { left, right } => {
+                (left.to_string(), right.to_string())
+            }
+            FieldDiff::PreviousOutput { left, right, .. } => (outpoint(left), outpoint(right)),
+            FieldDiff::ScriptSig { left, right, .. }
+            | FieldDiff::ScriptPubKey { left, right, .. } => {
+                (hex::encode(&left.bytes), hex::encode(&right.bytes))
+            }
+            FieldDiff::Witness { left, right, .. } => (stack_hex(left), stack_hex(right)),
+            FieldDiff::Value { left, right, .. } => (left.to_string(), right.to_string()),
+        };
+        write!(f, "{}: {} != {}", self.path(), left, right)
+    }
+}
+
+/// A transaction together with the value of each output it spends, in
+/// input order. The values live here rather than on the inputs, so the
+/// transaction and its consensus encoding are untouched.
+#[derive(Debug, Clone)]
+pub struct SigningContext<'a> {
+    tx: &'a BitcoinTransaction,
+    prevout_values: Vec<u64>,
+}
+
+impl<'a> SigningContext<'a> {
+    /// `AmountCountMismatch` unless there is one value per input
+    pub fn new(tx: &'a BitcoinTransaction, prevout_values: Vec<u64>) -> Result<Self, BitcoinError> {
+        if prevout_values.len() != tx.inputs.len() {
+            return Err(BitcoinError::AmountCountMismatch {
+                expected: tx.inputs.len(),
+                actual: prevout_values.len(),
+            });
+        }
+        Ok(SigningContext { tx, prevout_values })
+    }
+
+    pub fn transaction(&self) -> &'a BitcoinTransaction {
+        self.tx
+    }
+
+    pub fn prevout_value(&self, input_index: usize) -> Option<u64> {
+        self.prevout_values.get(input_index).copied()
+    }
+
+    /// `BitcoinTransaction::segwit_sighash` using the stored prevout value
+    pub fn segwit_sighash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        sighash_type: SighashType,
+    ) -> Result<[u8; 32], BitcoinError> {
+        self.tx.check_input_index(input_index)?;
+        let amount = self.prevout_values[input_index];
+        self.tx
+            .segwit_sighash(input_index, script_code, amount, sighash_type)
+    }
+
+    pub fn fee(&self) -> Result<u64, BitcoinError> {
+        self.tx.fee(&self.prevout_values)
+    }
+}
+
+/// Magic prefix of `BitcoinTransaction::to_framed_bytes`
+pub const FRAME_MAGIC: [u8; 4] = *b"BTXF";
+/// Current version of the framed format
+pub const FRAME_VERSION: u8 = 1;
+const FRAME_HEADER_LEN: usize = 9;
+
+/// Largest serialized transaction a block can hold (4M weight units)
+pub const MAX_TX_SIZE: usize = 4_000_000;
+
+// Collects the bytes of one transaction from an async stream, so that the
+// normal parser can validate them once complete
+#[cfg(feature = "tokio")]
+struct AsyncFramer<'r, R> {
+    reader: &'r mut R,
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + Unpin> AsyncFramer<'_, R> {
+    async fn take(&mut self, n: u64) -> Result<(), BitcoinError> {
+        use tokio::io::AsyncReadExt;
+
+        // Bounds the allocation an attacker-chosen length can cause
+        let start = self.buf.len();
+        let end = usize::try_from(n)
+            .ok()
+            .and_then(|n| start.checked_add(n))
+            .filter(|&end| end <= MAX_TX_SIZE)
+            .ok_or(BitcoinError::InvalidFormat)?;
+        self.buf.resize(end, 0);
+        self.reader
+            .read_exact(&mut self.buf[start..])
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => BitcoinError::InsufficientBytes,
+                _ => BitcoinError::Io(e.to_string()),
+            })?;
+        Ok(())
+    }
+
+    async fn compact_size(&mut self) -> Result<u64, BitcoinError> {
+        let start = self.buf.len();
+        self.take(1).await?;
+        self.finish_compact_size(start).await
+    }
+
+    // Reads the rest of a CompactSize whose prefix is already at `start`
+    async fn finish_compact_size(&mut self, start: usize) -> Result<u64, BitcoinError> {
+        let extra = CompactSize::len_from_prefix(self.buf[start]) - 1;
+        self.take(extra as u64).await?;
+        Ok(BitcoinReader::new(&self.buf[start..])
+            .read_compact_size()?
+            .value)
+    }
+
+    async fn script(&mut self) -> Result<(), BitcoinError> {
+        let len = self.compact_size().await?;
+        if len > MAX_SCRIPT_SIZE as u64 {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        self.take(len).await
+    }
+}
+
+/// Inputs and outputs `Display` lists before summarizing the rest
+pub const DISPLAY_ITEM_LIMIT: usize = 10;
+
+impl fmt::Display for BitcoinTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.display_with_limit(DISPLAY_ITEM_LIMIT), f)
+    }
+}
+
+impl BitcoinTransaction {
+    /// Like `Display`, but listing at most `limit` inputs and outputs
+    pub fn display_with_limit(&self, limit: usize) -> TransactionDisplay<'_> {
+        TransactionDisplay { tx: self, limit }
+    }
+}
+
+/// `Display` adapter from `BitcoinTransaction::display_with_limit`. Inputs
+/// and outputs past the limit are folded into an "... and N more" line;
+/// the alternate flag `{:#}` lists everything and disassembles scripts.
+#[derive(Debug, Clone, Copy)]
+pub struct TransactionDisplay<'a> {
+    tx: &'a BitcoinTransaction,
+    limit: usize,
+}
+
+impl fmt::Display for TransactionDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tx = self.tx;
+        let limit = if f.alternate() {
+            usize::MAX
+        } else {
+            self.limit
+        };
+
+        writeln!(f, "Bitcoin Transaction:")?;
+        writeln!(f, "  Version: {}", tx.version)?;
+        writeln!(f, "  Inputs ({}): ", tx.inputs.len())?;
+
+        for (i, input) in tx.inputs.iter().enumerate().take(limit) {
+            writeln!(f, "    Input {}:", i)?;
+            writeln!(
+                f,
+                "      Previous Output Txid: {}",
+                hex::encode(input.previous_output.txid.0)
+            )?;
+            writeln!(
+                f,
+                "      Previous Output Vout: {}",
+                input.previous_output.vout
+            )?;
+            if f.alternate() {
+                writeln!(f, "      Script Sig: {:#}", input.script_sig)?;
+            } else {
+                writeln!(f, "      Script Sig: {}", input.script_sig)?;
+            }
+            writeln!(f, "      Sequence: 0x{:08X}", input.sequence)?;
+            if let Some(witness) = tx.witnesses.get(i).filter(|w| !w.is_empty()) {
+                let items: Vec<String> = witness.iter().map(hex::encode).collect();
+                writeln!(f, "      Witness: {}", items.join(" "))?;
+            }
+        }
+        if tx.inputs.len() > limit {
+            writeln!(f, "    ... and {} more", tx.inputs.len() - limit)?;
+        }
+
+        writeln!(f, "  Outputs ({}): ", tx.outputs.len())?;
+
+        for (i, output) in tx.outputs.iter().enumerate().take(limit) {
+            writeln!(f, "    Output {}:", i)?;
+            writeln!(f, "      Value: {}", output.value)?;
+            if f.alternate() {
+                writeln!(f, "      Script PubKey: {:#}", output.script_pubkey)?;
+            } else {
+                writeln!(f, "      Script PubKey: {}", output.script_pubkey)?;
+            }
+        }
+        if tx.outputs.len() > limit {
+            writeln!(f, "    ... and {} more", tx.outputs.len() - limit)?;
+        }
+
+        write!(f, "  Lock Time: {}", tx.lock_time)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    version: u32,
+    inputs: Vec<TransactionInput>,
+    outputs: Vec<TransactionOutput>,
+    lock_time: u32,
+    // Serialized size of every input and output added so far, counts excluded
+    items_size: usize,
+}
+
+impl Default for TransactionBuilder {
+    fn default() -> Self {
+        TransactionBuilder::new()
+    }
+}
+
+impl TransactionBuilder {
+    /// Starts a transaction with version 2 and lock_time 0
+    ///
+    /// ```
+    /// use rust_week_3_exercises::{OutPoint, Script, TransactionBuilder};
+    ///
+    /// let tx = TransactionBuilder::new()
+    ///     .add_input(OutPoint::new([0u8; 32], 0), Script::new(vec![]), 0xFFFFFFFF)
+    ///     .add_output(50_000, Script::new(vec![0x51]))
+    ///     .lock_time(800_000)
+    ///     .build();
+    ///
+    /// assert_eq!(tx.version, 2);
+    /// assert_eq!(tx.inputs.len(), 1);
+    /// assert_eq!(tx.outputs[0].value, 50_000);
+    /// ```
+    pub fn new() -> Self {
+        TransactionBuilder {
+            version: 2,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            lock_time: 0,
+            items_size: 0,
+        }
+    }
+
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn add_input(
+        mut self,
+        previous_output: OutPoint,
+        script_sig: Script,
+        sequence: u32,
+    ) -> Self {
+        let input = TransactionInput::new(previous_output, script_sig, sequence);
+        self.items_size += input.wire_size();
+        self.inputs.push(input);
+        self
+    }
+
+    pub fn add_output(mut self, value: u64, script_pubkey: Script) -> Self {
+        let output = TransactionOutput::new(value, script_pubkey);
+        self.items_size += output.wire_size();
+        self.outputs.push(output);
+        self
+    }
+
+    /// Serialized length the built transaction will have, kept up to date as
+    /// inputs and outputs are added so coin selection can check size without
+    /// re-serializing. The txid is only available after `build`.
+    pub fn running_size(&self) -> usize {
+        4 + CompactSize::from_len(&self.inputs).encoded_len()
+            + CompactSize::from_len(&self.outputs).encoded_len()
+            + self.items_size
+            + 4
+    }
+
+    pub fn lock_time(mut self, lock_time: u32) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    pub fn build(self) -> BitcoinTransaction {
+        BitcoinTransaction::new(self.version, self.inputs, self.outputs, self.lock_time)
+    }
+}
+
+pub const PSBT_MAGIC: [u8; 5] = [0x70, 0x73, 0x62, 0x74, 0xff];
+pub const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+pub const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+
+/// Key-value pairs of one PSBT section. Keys include their leading type byte.
+pub type PsbtMap = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Partially signed transaction (BIP-174, version 0). Only the unsigned
+/// transaction and witness UTXOs are interpreted; every other entry is kept
+/// as raw bytes and written back unchanged.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Psbt {
+    pub unsigned_tx: BitcoinTransaction,
+    /// Global entries other than the unsigned transaction
+    pub global: PsbtMap,
+    pub inputs: Vec<PsbtMap>,
+    pub outputs: Vec<PsbtMap>,
+}
+
+impl Psbt {
+    /// Wraps a transaction with empty maps. Fails with `InvalidFormat` if any
+    /// input already has a scriptSig or witness.
+    pub fn new(unsigned_tx: BitcoinTransaction) -> Result<Self, BitcoinError> {
+        Psbt::check_unsigned(&unsigned_tx)?;
+        Ok(Psbt {
+            inputs: vec![PsbtMap::new(); unsigned_tx.inputs.len()],
+            outputs: vec![PsbtMap::new(); unsigned_tx.outputs.len()],
+            global: PsbtMap::new(),
+            unsigned_tx,
+        })
+    }
+
+    fn check_unsigned(tx: &BitcoinTransaction) -> Result<(), BitcoinError> {
+        if tx.is_segwit() || tx.inputs.iter().any(|input| !input.script_sig.is_empty()) {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Ok(())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), BitcoinError> {
+        ensure_non_empty(bytes)?;
+        let mut reader = BitcoinReader::new(bytes);
+        let psbt = Psbt::read_from(&mut reader).map_err(|e| e.at(reader.position()))?;
+        Ok((psbt, reader.position()))
+    }
+
+    fn read_from(reader: &mut BitcoinReader<'_>) -> Result<Self, BitcoinError> {
+        if reader.read_exact(PSBT_MAGIC.len())? != PSBT_MAGIC {
+            return Err(BitcoinError::InvalidFormat);
+        }
+
+        let mut global = Psbt::read_map(reader)?;
+        let tx_bytes = global
+            .remove([PSBT_GLOBAL_UNSIGNED_TX].as_slice())
+            .ok_or(BitcoinError::InvalidFormat)?;
+        // Always non-witness serialization (BIP-174), so `00 01` is a zero
+        // input count and one output. Transaction errors are relative to the
+        // value, so drop their offset.
+        let (unsigned_tx, consumed) =
+            BitcoinTransaction::from_bytes_with_mode(&tx_bytes, SerializeMode::Legacy)
+                .map_err(|_| BitcoinError::InvalidFormat)?;
+        if consumed != tx_bytes.len() {
+            return Err(BitcoinError::InvalidFormat);
+        }
+        Psbt::check_unsigned(&unsigned_tx)?;
+
+        let inputs = (0..unsigned_tx.inputs.len())
+            .map(|_| Psbt::read_map(reader))
+            .collect::<Result<_, _>>()?;
+        let outputs = (0..unsigned_tx.outputs.len())
+            .map(|_| Psbt::read_map(reader))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Psbt {
+            unsigned_tx,
+            global,
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Reads key-value pairs up to the 0x00 separator, rejecting duplicate keys
+    fn read_map(reader: &mut BitcoinReader<'_>) -> Result<PsbtMap, BitcoinError> {
+        let mut map = PsbtMap::new();
+        loop {
+            let key_len = reader.read_count(1)?;
+            if key_len == 0 {
+                return Ok(map);
+            }
+            let key = reader.read_exact(key_len)?.to_vec();
+            let value = reader.read_var_bytes()?.to_vec();
+            if map.insert(key, value).is_some() {
+                return Err(BitcoinError::InvalidFormat);
+            }
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = PSBT_MAGIC.to_vec();
+
+        Psbt::write_pair(
+            &mut bytes,
+            &[PSBT_GLOBAL_UNSIGNED_TX],
+            &self.unsigned_tx.to_bytes_legacy(),
+        );
+        for map in core::iter::once(&self.global)
+            .chain(&self.inputs)
+            .chain(&self.outputs)
+        {
+            for (key, value) in map {
+                Psbt::write_pair(&mut bytes, key, value);
+            }
+            bytes.push(0x00);
+        }
+        bytes
+    }
+
+    fn write_pair(bytes: &mut Vec<u8>, key: &[u8], value: &[u8]) {
+        bytes.extend(CompactSize::from_len(key).to_bytes());
+        bytes.extend_from_slice(key);
+        bytes.extend(CompactSize::from_len(value).to_bytes());
+        bytes.extend_from_slice(value);
+    }
+
+    /// The output spent by input `index`, if a witness UTXO was recorded
+    pub fn witness_utxo(&self, index: usize) -> Result<Option<TransactionOutput>, BitcoinError> {
+        self.unsigned_tx.check_input_index(index)?;
+        match self.inputs[index].get([PSBT_IN_WITNESS_UTXO].as_slice()) {
+            Some(value) => decode_exact(value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_witness_utxo(
+        &mut self,
+        index: usize,
+        utxo: &TransactionOutput,
+    ) -> Result<(), BitcoinError> {
+        self.unsigned_tx.check_input_index(index)?;
+        self.inputs[index].insert(vec![PSBT_IN_WITNESS_UTXO], utxo.to_bytes());
+        Ok(())
+    }
+}
+
+/// Deterministic generators of valid values for property tests. Not
+/// cryptographically random; the same seed always yields the same values.
+#[cfg(feature = "test-utils")]
+pub mod test_utils {
+    use super::*;
+
+    /// xorshift64* generator
+    #[derive(Debug, Clone)]
+    pub struct Rng(u64);
+
+    impl Rng {
+        pub fn new(seed: u64) -> Self {
+            // A zero state would only ever produce zeros
+            Rng(seed.max(1))
+        }
+
+        pub fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        /// Uniform-ish value in `0..bound`; `bound` must be non-zero
+        pub fn below(&mut self, bound: u64) -> u64 {
+            self.next_u64() % bound
+        }
+
+        pub fn bytes(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next_u64() as u8).collect()
+        }
+    }
+
+    /// Picks one of the four encoding widths first, so every width is common
+    pub fn compact_size(rng: &mut Rng) -> CompactSize {
+        let value = match rng.below(4) {
+            0 => rng.below(0xFD),
+            1 => 0xFD + rng.below(0xFFFF - 0xFD + 1),
+            2 => 0x10000 + rng.below(0xFFFF_FFFF - 0x10000 + 1),
+            _ => 0x1_0000_0000 + rng.below(u64::MAX - 0xFFFF_FFFF),
+        };
+        CompactSize::new(value)
+    }
+
+    pub fn outpoint(rng: &mut Rng) -> OutPoint {
+        let txid = rng.bytes(32).try_into().expect("32 bytes");
+        OutPoint::new(txid, rng.next_u64() as u32)
+    }
+
+    pub fn script(rng: &mut Rng) -> Script {
+        let len = rng.below(100) as usize;
+        Script::new(rng.bytes(len))
+    }
+
+    /// A transaction with 1-3 inputs and 0-3 outputs that is segwit about half
+    /// the time. There is always an input, since a zero-input transaction
+    /// with outputs is indistinguishable from the segwit marker.
+    pub fn transaction(rng: &mut Rng) -> BitcoinTransaction {
+        let inputs: Vec<TransactionInput> = (0..1 + rng.below(3))
+            .map(|_| TransactionInput::new(outpoint(rng), script(rng), rng.next_u64() as u32))
+            .collect();
+        let outputs = (0..rng.below(4))
+            .map(|_| TransactionOutput::new(rng.next_u64(), script(rng)))
+            .collect();
+
+        let mut witnesses = Vec::new();
+        if rng.below(2) == 0 {
+            for _ in 0..inputs.len() {
+                let items: Vec<Vec<u8>> = (0..rng.below(4))
+                    .map(|_| {
+                        let len = rng.below(80) as usize;
+                        rng.bytes(len)
+                    })
+                    .collect();
+                witnesses.push(items);
+            }
+            // Witness serialization requires at least one non-empty stack
+            witnesses[0].push(rng.bytes(1));
+        }
+
+        let mut tx = BitcoinTransaction::new(
+            rng.next_u64() as u32,
+            inputs,
+            outputs,
+            rng.next_u64() as u32,
+        );
+        tx.witnesses = witnesses;
+        tx
+    }
+}
+
+// Conversions to and from rust-bitcoin. Witness stacks move between our
+// per-transaction `witnesses` list and each `TxIn`; a legacy transaction is
+// one with no witnesses at all on our side and empty ones on theirs.
+#[cfg(feature = "rust-bitcoin")]
+mod rust_bitcoin {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    impl From<OutPoint> for bitcoin::OutPoint {
+        fn from(outpoint: OutPoint) -> Self {
+            bitcoin::OutPoint {
+                txid: bitcoin::Txid::from_byte_array(outpoint.txid.0),
+                vout: outpoint.vout,
+            }
+        }
+    }
+
+    impl From<bitcoin::OutPoint> for OutPoint {
+        fn from(outpoint: bitcoin::OutPoint) -> Self {
+            OutPoint {
+                txid: Txid(outpoint.txid.to_byte_array()),
+                vout: outpoint.vout,
+            }
+        }
+    }
+
+    impl From<Script> for bitcoin::ScriptBuf {
+        fn from(script: Script) -> Self {
+            bitcoin::ScriptBuf::from_bytes(script.bytes)
+        }
+    }
+
+    impl From<bitcoin::ScriptBuf> for Script {
+        fn from(script: bitcoin::ScriptBuf) -> Self {
+            Script::new(script.into_bytes())
+        }
+    }
+
+    impl From<TransactionOutput> for bitcoin::TxOut {
+        fn from(output: TransactionOutput) -> Self {
+            bitcoin::TxOut {
+                value: bitcoin::Amount::from_sat(output.value),
+                script_pubkey: output.script_pubkey.into(),
+            }
+        }
+    }
+
+    impl From<bitcoin::TxOut> for TransactionOutput {
+        fn from(output: bitcoin::TxOut) -> Self {
+            TransactionOutput::new(output.value.to_sat(), output.script_pubkey.into())
+        }
+    }
+
+    /// Fails with `InvalidFormat` when there are more witness stacks than
+    /// inputs, since `bitcoin::Transaction` has nowhere to keep the extras.
+    impl TryFrom<BitcoinTransaction> for bitcoin::Transaction {
+        type Error = BitcoinError;
+
+        fn try_from(tx: BitcoinTransaction) -> Result<Self, BitcoinError> {
+            if tx.witnesses.len() > tx.inputs.len() {
+                return Err(BitcoinError::InvalidFormat);
+            }
+            let mut witnesses = tx.witnesses.into_iter();
+            let input = tx
+                .inputs
+                .into_iter()
+                .map(|input| bitcoin::TxIn {
+                    previous_output: input.previous_output.into(),
+                    script_sig: input.script_sig.into(),
+                    sequence: bitcoin::Sequence(input.sequence),
+                    witness: witnesses
+                        .next()
+                        .map(|stack| bitcoin::Witness::from_slice(&stack))
+                        .unwrap_or_default(),
+                })
+                .collect();
+            Ok(bitcoin::Transaction {
+                // Same four bytes on the wire; rust-bitcoin reads them as signed
+                version: bitcoin::transaction::Version(tx.version as i32),
+                lock_time: bitcoin::absolute::LockTime::from_consensus(tx.lock_time),
+                input,
+                output: tx.outputs.into_iter().map(Into::into).collect(),
+            })
+        }
+    }
+
+    impl From<bitcoin::Transaction> for BitcoinTransaction {
+        fn from(tx: bitcoin::Transaction) -> Self {
+            let segwit = tx.input.iter().any(|input| !input.witness.is_empty());
+            let mut witnesses = Vec::new();
+            let mut inputs = Vec::with_capacity(tx.input.len());
+            for input in tx.input {
+                if segwit {
+                    witnesses.push(input.witness.to_vec());
+                }
+                inputs.push(TransactionInput::new(
+                    input.previous_output.into(),
+                    input.script_sig.into(),
+                    input.sequence.0,
+                ));
+            }
+            let mut converted = BitcoinTransaction::new(
+                tx.version.0 as u32,
+                inputs,
+                tx.output.into_iter().map(Into::into).collect(),
+                tx.lock_time.to_consensus_u32(),
+            );
+            converted.witnesses = witnesses;
+            converted
+        }
     }
 }
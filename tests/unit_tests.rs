@@ -4,6 +4,13 @@ use rust_week_3_exercises::*;
 mod tests {
     use super::*;
 
+    // Non-segwit mainnet transaction 971ed48a62c143bbd9c87f4bafa2ef213cfa106c6e140f111931d0be307468dd
+    const LEGACY_TX_HEX: &str = "01000000010c7196428403d8b0c88fcb3ee8d64f56f55c8973c9ab7dd106bb4f3527f5888d000000006a4730440220503a696f55f2c00eee2ac5e65b17767cd88ed04866b5637d3c1d5d996a70656d02202c9aff698f343abb6d176704beda63fcdec503133ea4f6a5216b7f925fa9910c0121024d89b5a13d6521388969209df27a8469bd565aff10e8d42cef931fad5121bfb8ffffffff02b825b404000000001976a914ef79e7ee9fff98bcfd08473d2b76b02a48f8c69088ac0000000000000000296a2732363030393438363937313732333132373633313032313332353630353838373931323132373000000000";
+
+    // Segwit transaction with txid 9652aa62b0e748caeec40c4cb7bc17c6792435cc3dfe447dd1ca24f912a1c6ec
+    // and wtxid d6ac4a5e61657c4c604dcde855a1db74ec6b3e54f32695d72c5e11c7761ea1b4
+    const SEGWIT_TX_HEX: &str = "01000000000102ff34f95a672bb6a4f6ff4a7e90fa8c7b3be7e70ffc39bc99be3bda67942e836c0000000023220020cde476664d3fa347b8d54ef3aee33dcb686a65ced2b5207cbf4ec5eda6b9b46e4f414d4c934ad81d330314e888888e3bd22c7dde8aac2ca9227b30d7c40093248af7812201000000232200200af6f6a071a69d5417e592ed99d256ddfd8b3b2238ac73f5da1b06fc0b2e79d54f414d4c0ba0c8f505000000001976a914dcb5898d9036afad9209e6ff0086772795b1441088ac033c0f000000000017a914889f8c10ff2bd4bb9dabb68c5c0d700a46925e6c87033c0f000000000017a914889f8c10ff2bd4bb9dabb68c5c0d700a46925e6c87033c0f000000000017a914889f8c10ff2bd4bb9dabb68c5c0d700a46925e6c87033c0f000000000017a914889f8c10ff2bd4bb9dabb68c5c0d700a46925e6c87033c0f000000000017a914889f8c10ff2bd4bb9dabb68c5c0d700a46925e6c87033c0f000000000017a914889f8c10ff2bd4bb9dabb68c5c0d700a46925e6c87033c0f000000000017a914889f8c10ff2bd4bb9dabb68c5c0d700a46925e6c87033c0f000000000017a914889f8c10ff2bd4bb9dabb68c5c0d700a46925e6c87033c0f000000000017a914889f8c10ff2bd4bb9dabb68c5c0d700a46925e6c87033c0f000000000017a914889f8c10ff2bd4bb9dabb68c5c0d700a46925e6c87050047304402200380b8663e727d7e8d773530ef85d5f82c0b067c97ae927800a0876a1f01d8e2022021ee611ef6507dfd217add2cd60a8aea3cbcfec034da0bebf3312d19577b8c290147304402207bd9943ce1c2c5547b120683fd05d78d23d73be1a5b5a2074ff586b9c853ed4202202881dcf435088d663c9af7b23efb3c03b9dbc0c899b247aa94a74d9b4b3c84f501483045022100ba12bba745af3f18f6e56be70f8382ca8e107d1ed5ceaa3e8c360d5ecf78886f022069b38ebaac8fe6a6b97b497cbbb115f3176f7213540bef08f9292e5a72de52de01695321023c9cd9c6950ffee24772be948a45dc5ef1986271e46b686cb52007bac214395a2102756e27cb004af05a6e9faed81fd68ff69959e3c64ac8c9f6cd0e08fd0ad0e75d2103fa40da236bd82202a985a9104e851080b5940812685769202a3b43e4a8b13e6a53ae050048304502210098b9687b81d725a7970d1eee91ff6b89bc9832c2e0e3fb0d10eec143930b006f02206f77ce19dc58ecbfef9221f81daad90bb4f468df391212abc4f084fe2cc9bdef01483045022100e5479f81a3ad564103da5e2ec8e12f61f3ac8d312ab68763c1ddd7bae94c20610220789b81b7220b27b681b1b2e87198897376ba9d033bc387f084c8b8310c8539c201483045022100aa1cc48a2d256c0e556616444cc08ae4959d464e5ffff2ae09e3550bdab6ce9f02207192d5e3329a56ba7b1ead724634d104f1c3f8749fe6081e6233aee3e855817a016953210260de9cc68658c61af984e3ab0281d17cfca1cc035966d335f474932d5e6c5422210355fbb768ce3ce39360277345dbb5f376e706459e5a2b5e0e09a535e61690647021023222ceec58b94bd25925dd9743dae6b928737491bd940fc5dd7c6f5d5f2adc1e53ae00000000";
+
     fn dummy_txid(val: u8) -> [u8; 32] {
         let mut txid = [0u8; 32];
         txid[31] = val;
@@ -34,6 +41,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_compact_size_rejects_non_canonical() {
+        let non_canonical = vec![
+            vec![0xFD, 0x0A, 0x00],
+            vec![0xFD, 0xFC, 0x00],
+            vec![0xFE, 0xFF, 0xFF, 0x00, 0x00],
+            vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00],
+        ];
+
+        for bytes in non_canonical {
+            assert_eq!(
+                CompactSize::from_bytes(&bytes),
+                Err(BitcoinError::InvalidFormat)
+            );
+            let (decoded, consumed) = CompactSize::from_bytes_unchecked(&bytes).unwrap();
+            assert_eq!(consumed, bytes.len());
+            assert!(decoded.value <= 0xFFFFFFFF);
+        }
+
+        let (decoded, _) = CompactSize::from_bytes_unchecked(&[0xFD, 0x0A, 0x00]).unwrap();
+        assert_eq!(decoded.value, 10);
+    }
+
     #[test]
     fn test_outpoint_roundtrip() {
         let txid = dummy_txid(0xCC);
@@ -66,6 +96,22 @@ mod tests {
         assert_eq!(consumed, bytes.len());
     }
 
+    #[test]
+    fn test_tx_output_roundtrip() {
+        let script = Script::new(vec![0x76, 0xA9, 0x14, 0x88, 0xAC]);
+        let output = TransactionOutput::new(50_000, script);
+        let bytes = output.to_bytes();
+        assert_eq!(&bytes[0..8], &50_000u64.to_le_bytes());
+        let (parsed, consumed) = TransactionOutput::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, output);
+        assert_eq!(consumed, bytes.len());
+
+        assert_eq!(
+            TransactionOutput::from_bytes(&[0x01, 0x02, 0x03]),
+            Err(BitcoinError::InsufficientBytes)
+        );
+    }
+
     #[test]
     fn test_bitcoin_tx_roundtrip() {
         let inputs = vec![TransactionInput::new(
@@ -73,13 +119,39 @@ mod tests {
             Script::new(vec![0x01, 0x02]),
             0xFFFFFFFF,
         )];
-        let tx = BitcoinTransaction::new(2, inputs.clone(), 1000);
+        let tx = BitcoinTransaction::new(2, inputs.clone(), vec![], 1000);
         let bytes = tx.to_bytes();
         let (parsed, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
         assert_eq!(parsed, tx);
         assert_eq!(consumed, bytes.len());
     }
 
+    #[test]
+    fn test_bitcoin_tx_with_outputs_roundtrip() {
+        let inputs = vec![
+            TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x01, 0x02]),
+                0xFFFFFFFF,
+            ),
+            TransactionInput::new(
+                OutPoint::new(dummy_txid(2), 1),
+                Script::new(vec![0x03]),
+                0xFFFFFFFE,
+            ),
+        ];
+        let outputs = vec![
+            TransactionOutput::new(10_000, Script::new(vec![0x76, 0xA9, 0x14, 0x88, 0xAC])),
+            TransactionOutput::new(20_000, Script::new(vec![0x00, 0x14])),
+        ];
+        let tx = BitcoinTransaction::new(2, inputs, outputs, 500);
+        let bytes = tx.to_bytes();
+        let (parsed, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, tx);
+        assert_eq!(parsed.outputs.len(), 2);
+        assert_eq!(consumed, bytes.len());
+    }
+
     #[test]
     fn test_bitcoin_tx_json_serialization() {
         let input = TransactionInput::new(
@@ -87,7 +159,7 @@ mod tests {
             Script::new(vec![0xDE, 0xAD, 0xBE, 0xEF]),
             0xABCDEF01,
         );
-        let tx = BitcoinTransaction::new(1, vec![input], 999);
+        let tx = BitcoinTransaction::new(1, vec![input], vec![], 999);
 
         let json = serde_json::to_string_pretty(&tx).unwrap();
         let parsed: BitcoinTransaction = serde_json::from_str(&json).unwrap();
@@ -104,10 +176,2788 @@ mod tests {
             Script::new(vec![0x01, 0x02, 0x03]),
             0xFFFFFFFF,
         );
-        let tx = BitcoinTransaction::new(1, vec![input], 0);
+        let tx = BitcoinTransaction::new(1, vec![input], vec![], 0);
         let output = format!("{}", tx);
         assert!(output.contains("Version: 1"));
         assert!(output.contains("Lock Time: 0"));
         assert!(output.contains("Previous Output Vout: 7"));
     }
+
+    #[test]
+    fn test_txid_known_vector() {
+        let bytes = hex::decode(LEGACY_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            tx.txid_hex(),
+            "971ed48a62c143bbd9c87f4bafa2ef213cfa106c6e140f111931d0be307468dd"
+        );
+        let mut expected = tx.txid();
+        expected.reverse();
+        assert_eq!(hex::encode(expected), tx.txid_hex());
+    }
+
+    #[test]
+    fn test_txid_empty_inputs_is_deterministic() {
+        let tx = BitcoinTransaction::new(1, vec![], vec![], 0);
+        assert_eq!(tx.txid(), tx.clone().txid());
+        assert_ne!(
+            tx.txid(),
+            BitcoinTransaction::new(2, vec![], vec![], 0).txid()
+        );
+    }
+
+    #[test]
+    fn test_segwit_tx_roundtrip() {
+        let bytes = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let (tx, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert!(tx.is_segwit());
+        assert_eq!(tx.witnesses.len(), tx.inputs.len());
+        assert_eq!(tx.witnesses[0].len(), 5);
+        assert!(tx.witnesses[0][0].is_empty());
+        assert_eq!(tx.to_bytes(), bytes);
+        assert_eq!(
+            tx.txid_hex(),
+            "9652aa62b0e748caeec40c4cb7bc17c6792435cc3dfe447dd1ca24f912a1c6ec"
+        );
+    }
+
+    #[test]
+    fn test_legacy_zero_input_tx_not_parsed_as_segwit() {
+        let outputs = vec![
+            TransactionOutput::new(1, Script::new(vec![0x51])),
+            TransactionOutput::new(2, Script::new(vec![0x52])),
+        ];
+        for outputs in [vec![], outputs] {
+            let tx = BitcoinTransaction::new(1, vec![], outputs, 0);
+            let bytes = tx.to_bytes();
+            assert_eq!(bytes[4], 0x00);
+            let (parsed, consumed) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+            assert!(!parsed.is_segwit());
+            assert_eq!(parsed, tx);
+            assert_eq!(consumed, bytes.len());
+        }
+    }
+
+    #[test]
+    fn test_segwit_marker_without_witness_rejected() {
+        let mut tx = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![]),
+                0xFFFFFFFF,
+            )],
+            vec![TransactionOutput::new(1000, Script::new(vec![0x51]))],
+            0,
+        );
+        tx.witnesses = vec![vec![vec![0xAA; 3]]];
+        let mut bytes = tx.to_bytes();
+        assert_eq!(&bytes[4..6], &[0x00, 0x01]);
+
+        // Replace the single witness stack with an empty one
+        let witness_start = bytes.len() - 4 - 5;
+        bytes.splice(witness_start..bytes.len() - 4, [0x00]);
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&bytes),
+            Err(BitcoinError::ParseError {
+                offset: bytes.len() - 5,
+                kind: ErrorKind::InvalidFormat,
+            })
+        );
+    }
+
+    #[test]
+    fn test_wtxid_known_vectors() {
+        let bytes = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            tx.wtxid_hex(),
+            "d6ac4a5e61657c4c604dcde855a1db74ec6b3e54f32695d72c5e11c7761ea1b4"
+        );
+        assert_ne!(tx.wtxid(), tx.txid());
+
+        let bytes = hex::decode(LEGACY_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(tx.wtxid(), tx.txid());
+        assert_eq!(tx.wtxid_hex(), tx.txid_hex());
+    }
+
+    #[test]
+    fn test_bitcoin_reader_sequential_reads() {
+        let outpoint = OutPoint::new(dummy_txid(9), 4);
+        let script = Script::new(vec![0xAA, 0xBB]);
+        let mut bytes = 7u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&CompactSize::new(300).to_bytes());
+        bytes.extend_from_slice(&script.to_bytes());
+        bytes.extend_from_slice(&outpoint.to_bytes());
+        bytes.push(0x42);
+
+        let mut reader = BitcoinReader::new(&bytes);
+        assert_eq!(reader.read_u32_le().unwrap(), 7);
+        assert_eq!(reader.read_compact_size().unwrap(), CompactSize::new(300));
+        assert_eq!(reader.read_script().unwrap(), script);
+        assert_eq!(reader.read_outpoint().unwrap(), outpoint);
+        assert_eq!(reader.position(), bytes.len() - 1);
+        assert_eq!(reader.read_exact(1).unwrap(), &[0x42]);
+        assert_eq!(reader.remaining(), 0);
+        assert_eq!(reader.read_u32_le(), Err(BitcoinError::InsufficientBytes));
+        assert_eq!(reader.read_exact(1), Err(BitcoinError::InsufficientBytes));
+    }
+
+    #[test]
+    fn test_huge_counts_rejected_without_allocating() {
+        // Version followed by a CompactSize claiming u64::MAX inputs
+        let mut bytes = vec![0x01, 0x00, 0x00, 0x00];
+        bytes.extend_from_slice(&[0xFF; 9]);
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&bytes),
+            Err(BitcoinError::ParseError {
+                offset: 4,
+                kind: ErrorKind::InvalidFormat,
+            })
+        );
+
+        // Zero inputs followed by a huge output count
+        let mut bytes = vec![0x01, 0x00, 0x00, 0x00, 0x00];
+        bytes.extend_from_slice(&[0xFE, 0xFF, 0xFF, 0xFF, 0xFF]);
+        bytes.extend_from_slice(&[0x00; 64]);
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&bytes),
+            Err(BitcoinError::ParseError {
+                offset: 5,
+                kind: ErrorKind::InvalidFormat,
+            })
+        );
+
+        // Script length prefix larger than the buffer
+        let mut bytes = vec![0xFF];
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(Script::from_bytes(&bytes), Err(BitcoinError::InvalidFormat));
+
+        // Two inputs claimed but only enough bytes for one
+        let input = TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::new(vec![]), 0);
+        let mut bytes = vec![0x01, 0x00, 0x00, 0x00, 0x02];
+        bytes.extend_from_slice(&input.to_bytes());
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&bytes),
+            Err(BitcoinError::ParseError {
+                offset: 4,
+                kind: ErrorKind::InvalidFormat,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_error_reports_offset() {
+        let bytes = hex::decode(LEGACY_TX_HEX).unwrap();
+
+        // Cut the transaction inside the first input's sequence
+        let truncated = &bytes[..150];
+        let err = BitcoinTransaction::from_bytes(truncated).unwrap_err();
+        assert_eq!(
+            err,
+            BitcoinError::ParseError {
+                offset: 148,
+                kind: ErrorKind::InsufficientBytes,
+            }
+        );
+        assert_eq!(err.offset(), Some(148));
+        assert_eq!(err.to_string(), "insufficient bytes at byte offset 148");
+
+        // Component parsers keep the bare variants
+        assert_eq!(
+            TransactionInput::from_bytes(&bytes[5..150]),
+            Err(BitcoinError::InsufficientBytes)
+        );
+        assert_eq!(BitcoinError::InvalidFormat.offset(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_bitcoin_error_display_and_std_error() {
+        assert_eq!(
+            BitcoinError::InsufficientBytes.to_string(),
+            "insufficient bytes"
+        );
+        assert_eq!(BitcoinError::InvalidFormat.to_string(), "invalid format");
+
+        fn parse(bytes: &[u8]) -> Result<Script, Box<dyn std::error::Error>> {
+            let (script, _) = Script::from_bytes(bytes)?;
+            Ok(script)
+        }
+        let err = parse(&[0x05, 0x01]).unwrap_err();
+        assert_eq!(err.to_string(), "invalid format");
+        assert!(err.downcast_ref::<BitcoinError>().is_some());
+    }
+
+    #[test]
+    fn test_bitcoin_tx_hex_roundtrip() {
+        for raw in [LEGACY_TX_HEX, SEGWIT_TX_HEX] {
+            let tx = BitcoinTransaction::from_hex(raw).unwrap();
+            assert_eq!(tx.to_hex(), raw);
+        }
+
+        let tx = BitcoinTransaction::from_hex(&LEGACY_TX_HEX.to_uppercase()).unwrap();
+        assert_eq!(tx.to_hex(), LEGACY_TX_HEX);
+    }
+
+    #[test]
+    fn test_bitcoin_tx_from_hex_rejects_bad_input() {
+        assert_eq!(
+            BitcoinTransaction::from_hex(&LEGACY_TX_HEX[1..]),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(
+            BitcoinTransaction::from_hex("zz000000"),
+            Err(BitcoinError::InvalidFormat)
+        );
+
+        let with_trailing = format!("{}00", LEGACY_TX_HEX);
+        assert_eq!(
+            BitcoinTransaction::from_hex(&with_trailing),
+            Err(BitcoinError::ParseError {
+                offset: LEGACY_TX_HEX.len() / 2,
+                kind: ErrorKind::InvalidFormat,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_exact_rejects_trailing_byte() {
+        let mut bytes = hex::decode(LEGACY_TX_HEX).unwrap();
+        let tx = BitcoinTransaction::parse_exact(&bytes).unwrap();
+        assert_eq!(tx.to_bytes(), bytes);
+
+        bytes.push(0x00);
+        let err = BitcoinTransaction::parse_exact(&bytes).unwrap_err();
+        assert_eq!(err.offset(), Some(bytes.len() - 1));
+        assert!(BitcoinTransaction::from_bytes(&bytes).is_ok());
+    }
+
+    #[test]
+    fn test_transaction_builder_one_in_one_out() {
+        let outpoint = OutPoint::new(dummy_txid(3), 1);
+        let script_pubkey = Script::new(vec![0x00, 0x14]);
+        let tx = TransactionBuilder::new()
+            .add_input(outpoint.clone(), Script::new(vec![]), 0xFFFFFFFD)
+            .add_output(12_345, script_pubkey.clone())
+            .build();
+
+        let expected = BitcoinTransaction::new(
+            2,
+            vec![TransactionInput::new(
+                outpoint,
+                Script::new(vec![]),
+                0xFFFFFFFD,
+            )],
+            vec![TransactionOutput::new(12_345, script_pubkey)],
+            0,
+        );
+        assert_eq!(tx, expected);
+
+        let tx = TransactionBuilder::new().version(1).lock_time(42).build();
+        assert_eq!(tx.version, 1);
+        assert_eq!(tx.lock_time, 42);
+    }
+
+    #[test]
+    fn test_size_weight_and_vsize() {
+        let tx = BitcoinTransaction::from_hex(SEGWIT_TX_HEX).unwrap();
+        assert_eq!(tx.size(), SEGWIT_TX_HEX.len() / 2);
+        assert!(tx.base_size() < tx.size());
+        assert_eq!(tx.weight(), 2718);
+        assert_eq!(tx.vsize(), 680);
+
+        let tx = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        assert_eq!(tx.base_size(), tx.size());
+        assert_eq!(tx.weight(), tx.size() * 4);
+        assert_eq!(tx.vsize(), tx.size());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_compact_size_from_reader() {
+        let mut stream = Vec::new();
+        for value in [7u64, 0xFD, 0x10000, 0x100000000] {
+            stream.extend_from_slice(&CompactSize::new(value).to_bytes());
+        }
+        stream.push(0xAB);
+
+        let mut cursor = std::io::Cursor::new(stream);
+        for value in [7u64, 0xFD, 0x10000, 0x100000000] {
+            assert_eq!(CompactSize::from_reader(&mut cursor).unwrap().value, value);
+        }
+        assert_eq!(CompactSize::from_reader(&mut cursor).unwrap().value, 0xAB);
+
+        let err = CompactSize::from_reader(&mut cursor).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        let mut truncated = std::io::Cursor::new(vec![0xFE, 0x01, 0x02]);
+        let err = CompactSize::from_reader(&mut truncated).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        let mut non_canonical = std::io::Cursor::new(vec![0xFD, 0x0A, 0x00]);
+        let err = CompactSize::from_reader(&mut non_canonical).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_consensus_encode_matches_to_bytes() {
+        for raw in [LEGACY_TX_HEX, SEGWIT_TX_HEX] {
+            let expected = hex::decode(raw).unwrap();
+            let tx = BitcoinTransaction::from_bytes(&expected).unwrap().0;
+
+            let mut buf = Vec::new();
+            let written = tx.consensus_encode(&mut buf).unwrap();
+            assert_eq!(buf, expected);
+            assert_eq!(written, expected.len());
+            assert_eq!(tx.to_bytes(), expected);
+
+            for input in &tx.inputs {
+                let mut buf = Vec::new();
+                assert_eq!(input.consensus_encode(&mut buf).unwrap(), buf.len());
+                assert_eq!(buf, input.to_bytes());
+            }
+            for output in &tx.outputs {
+                let mut buf = Vec::new();
+                assert_eq!(output.consensus_encode(&mut buf).unwrap(), buf.len());
+                assert_eq!(buf, output.to_bytes());
+            }
+        }
+
+        let mut buf = Vec::new();
+        assert_eq!(
+            CompactSize::new(0x10000)
+                .consensus_encode(&mut buf)
+                .unwrap(),
+            5
+        );
+        assert_eq!(buf, vec![0xFE, 0x00, 0x00, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn test_txid_display_byte_order() {
+        let txid = Txid(dummy_txid(0x01));
+        let internal = serde_json::to_string(&txid).unwrap();
+        let display = txid.to_display_hex();
+        assert_eq!(internal.trim_matches('"'), hex::encode(dummy_txid(0x01)));
+        assert!(display.starts_with("01"));
+        assert_ne!(internal.trim_matches('"'), display);
+
+        assert_eq!(Txid::from_display_hex(&display).unwrap(), txid);
+        assert_eq!(
+            Txid::from_display_hex("abcd"),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(
+            Txid::from_display_hex(&"g".repeat(64)),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_script_disasm_p2pkh() {
+        let script =
+            Script::new(hex::decode("76a91489abcdefabbaabbaabbaabbaabbaabbaabbaabba88ac").unwrap());
+        assert_eq!(
+            script.disasm().unwrap(),
+            "OP_DUP OP_HASH160 OP_PUSHBYTES_20 89abcdefabbaabbaabbaabbaabbaabbaabbaabba \
+             OP_EQUALVERIFY OP_CHECKSIG"
+        );
+    }
+
+    #[test]
+    fn test_script_disasm_pushdata_and_unknown() {
+        let script = Script::new(vec![0x00, 0x4c, 0x02, 0xAB, 0xCD, 0x6a, 0xc0]);
+        assert_eq!(
+            script.disasm().unwrap(),
+            "OP_0 OP_PUSHDATA1 abcd OP_RETURN OP_UNKNOWN(0xc0)"
+        );
+
+        let truncated = Script::new(vec![0x4d, 0x05, 0x00, 0x01]);
+        assert_eq!(truncated.disasm(), Err(BitcoinError::InvalidFormat));
+        let truncated = Script::new(vec![0x4c]);
+        assert_eq!(truncated.disasm(), Err(BitcoinError::InvalidFormat));
+    }
+
+    #[test]
+    fn test_script_type_classification() {
+        let hash20 = [0x11u8; 20];
+        let hash32 = [0x22u8; 32];
+        let build = |prefix: &[u8], body: &[u8], suffix: &[u8]| {
+            Script::new([prefix, body, suffix].concat())
+        };
+
+        let cases = vec![
+            (
+                build(&[0x76, 0xa9, 0x14], &hash20, &[0x88, 0xac]),
+                ScriptType::P2pkh,
+            ),
+            (build(&[0xa9, 0x14], &hash20, &[0x87]), ScriptType::P2sh),
+            (build(&[0x00, 0x14], &hash20, &[]), ScriptType::P2wpkh),
+            (build(&[0x00, 0x20], &hash32, &[]), ScriptType::P2wsh),
+            (build(&[0x51, 0x20], &hash32, &[]), ScriptType::P2tr),
+            (
+                build(&[0x6a, 0x04], &[0xde, 0xad, 0xbe, 0xef], &[]),
+                ScriptType::OpReturn,
+            ),
+            (build(&[0x00, 0x14], &hash32, &[]), ScriptType::NonStandard),
+            (
+                build(&[0x76, 0xa9, 0x14], &hash20, &[0x88]),
+                ScriptType::NonStandard,
+            ),
+            (Script::new(vec![]), ScriptType::NonStandard),
+        ];
+
+        for (script, expected) in cases {
+            assert_eq!(script.script_type(), expected);
+        }
+    }
+
+    #[test]
+    fn test_input_and_output_iterators() {
+        let tx = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        assert_eq!(tx.input_count(), 1);
+        assert_eq!(tx.output_count(), 2);
+        assert_eq!(tx.inputs().count(), tx.input_count());
+        assert_eq!(
+            tx.outputs().map(|output| output.value).collect::<Vec<_>>(),
+            vec![78_915_000, 0]
+        );
+        assert!(tx.inputs().all(|input| input.sequence == 0xFFFFFFFF));
+    }
+
+    #[test]
+    fn test_op_return_data() {
+        let payload = [0x42u8; 80];
+        let mut bytes = vec![0x6a, 0x4c, 80];
+        bytes.extend_from_slice(&payload);
+        let script = Script::new(bytes);
+        assert_eq!(script.op_return_data(), Some(&payload[..]));
+
+        let multi_push = Script::new(vec![0x6a, 0x02, 0xAA, 0xBB, 0x01, 0xCC]);
+        assert_eq!(multi_push.op_return_data(), Some(&[0xAA, 0xBB][..]));
+
+        let bare = Script::new(vec![0x6a]);
+        assert_eq!(bare.op_return_data(), Some(&[][..]));
+
+        let p2wpkh = Script::new([&[0x00, 0x14][..], &[0x11; 20]].concat());
+        assert_eq!(p2wpkh.op_return_data(), None);
+        assert_eq!(Script::new(vec![0x6a, 0x76]).op_return_data(), None);
+        assert_eq!(Script::new(vec![0x6a, 0x05, 0x01]).op_return_data(), None);
+    }
+
+    #[test]
+    fn test_script_length_cap() {
+        // A 32-bit sized length that no buffer can satisfy
+        let huge = [0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+        assert_eq!(Script::from_bytes(&huge), Err(BitcoinError::InvalidFormat));
+
+        let mut bytes = CompactSize::new(10_001).to_bytes();
+        bytes.extend_from_slice(&[0x51; 10_001]);
+        assert_eq!(Script::from_bytes(&bytes), Err(BitcoinError::InvalidFormat));
+
+        let mut reader = BitcoinReader::new(&bytes).with_max_script_len(20_000);
+        assert_eq!(reader.read_script().unwrap().len(), 10_001);
+
+        let at_limit = Script::new(vec![0x51; MAX_SCRIPT_SIZE]);
+        let (parsed, _) = Script::from_bytes(&at_limit.to_bytes()).unwrap();
+        assert_eq!(parsed, at_limit);
+    }
+
+    #[test]
+    fn test_outpoint_hash_and_ordering() {
+        use std::collections::{BTreeMap, HashSet};
+
+        let a0 = OutPoint::new(dummy_txid(1), 0);
+        let a1 = OutPoint::new(dummy_txid(1), 1);
+        let b0 = OutPoint::new(dummy_txid(2), 0);
+
+        let mut set = HashSet::new();
+        assert!(set.insert(a0.clone()));
+        assert!(set.insert(b0.clone()));
+        assert!(!set.insert(OutPoint::new(dummy_txid(1), 0)));
+        assert_eq!(set.len(), 2);
+
+        let mut utxos = BTreeMap::new();
+        utxos.insert(b0.clone(), 300u64);
+        utxos.insert(a1.clone(), 200);
+        utxos.insert(a0.clone(), 100);
+        let keys: Vec<_> = utxos.keys().cloned().collect();
+        assert_eq!(keys, vec![a0, a1, b0]);
+        assert!(Txid(dummy_txid(1)) < Txid(dummy_txid(2)));
+    }
+
+    #[test]
+    fn test_legacy_sighash_vectors() {
+        let tx = BitcoinTransaction::from_hex(SEGWIT_TX_HEX).unwrap();
+        let script_code =
+            Script::new(hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap());
+
+        // Reference values computed with rust-bitcoin's SighashCache
+        let vectors = [
+            (
+                1,
+                SighashType::All,
+                "5f44ebb9e4f5093e2c5ff59fd092af03e8227c90fc0207ebc70210e0c8370dd3",
+            ),
+            (
+                1,
+                SighashType::None,
+                "df8a5cab28e468e631ef1528959f39acca6fa3e20121bb85b0b1bd969b3ec5b6",
+            ),
+            (
+                1,
+                SighashType::Single,
+                "37257f346ea39d54441444e0592a7706d478b24484145ac4e212d175c1992484",
+            ),
+            (
+                1,
+                SighashType::AllPlusAnyoneCanPay,
+                "322743f1cb4725928adf3217d951cb43c0543aeba5489f48ed6c5690b5fe1ec1",
+            ),
+            (
+                0,
+                SighashType::SinglePlusAnyoneCanPay,
+                "e7a5cbf63efa21b38394ad8651365927dba7baf725868f7ddac79683aee4eeff",
+            ),
+            (
+                0,
+                SighashType::NonePlusAnyoneCanPay,
+                "c637428bd89e894eb967e97922b34f5d1473ad9146b6357a45addb6258720d35",
+            ),
+        ];
+        for (index, sighash_type, expected) in vectors {
+            let hash = tx
+                .legacy_sighash(index, &script_code, sighash_type)
+                .unwrap();
+            assert_eq!(hex::encode(hash), expected);
+        }
+
+        assert_eq!(
+            tx.legacy_sighash(2, &script_code, SighashType::All),
+            Err(BitcoinError::InputIndexOutOfRange { index: 2, count: 2 })
+        );
+    }
+
+    #[test]
+    fn test_legacy_sighash_single_without_output() {
+        let input = TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::new(vec![]), 0);
+        let tx = BitcoinTransaction::new(1, vec![input.clone(), input], vec![], 0);
+        let hash = tx
+            .legacy_sighash(1, &Script::new(vec![0x51]), SighashType::Single)
+            .unwrap();
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert_eq!(hash, one);
+    }
+
+    #[test]
+    fn test_segwit_sighash_bip143_vectors() {
+        // Native P2WPKH example from BIP-143
+        let tx = BitcoinTransaction::from_hex(
+            "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000\
+             eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ff\
+             ffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d\
+             000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000",
+        )
+        .unwrap();
+        let script_code =
+            Script::new(hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap());
+        let amount = 600_000_000;
+
+        let hash = tx
+            .segwit_sighash(1, &script_code, amount, SighashType::All)
+            .unwrap();
+        assert_eq!(
+            hex::encode(hash),
+            "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670"
+        );
+
+        // Other modes, cross-checked against rust-bitcoin
+        let vectors = [
+            (
+                SighashType::None,
+                "6ff11a9b87fb510a3a31af006bd3811b632f8a39d88a2bfda49cee203dcc356e",
+            ),
+            (
+                SighashType::Single,
+                "f4fe57286dd2ca8ac0e3dfccd54c352fcdcacbed80f194e264b75d7a7c74e4ce",
+            ),
+            (
+                SighashType::AllPlusAnyoneCanPay,
+                "fc5b6bbc855883bcfdaefb77071740ccde4929f15e6a13286584e779b2529d91",
+            ),
+            (
+                SighashType::SinglePlusAnyoneCanPay,
+                "79ff9ff708f79ce8f7a4f90d62028533a99d7340b7fb3d819dfd9a599a78e39c",
+            ),
+            (
+                SighashType::NonePlusAnyoneCanPay,
+                "4abb5ef58a968f8e1ab88a9fb72f2ce74b3022e65d334ac7b8aeda747515dc15",
+            ),
+        ];
+        for (sighash_type, expected) in vectors {
+            let hash = tx
+                .segwit_sighash(1, &script_code, amount, sighash_type)
+                .unwrap();
+            assert_eq!(hex::encode(hash), expected);
+        }
+
+        assert!(
+            tx.segwit_sighash(5, &script_code, amount, SighashType::All)
+                .is_err()
+        );
+    }
+
+    fn assert_codec_roundtrip<T>(value: T)
+    where
+        T: Encodable + Decodable + PartialEq + std::fmt::Debug,
+    {
+        let bytes = value.encode();
+        let (decoded, consumed) = T::decode(&bytes).unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!(consumed, bytes.len());
+    }
+
+    #[test]
+    fn test_generic_encodable_decodable() {
+        assert_codec_roundtrip(CompactSize::new(0x1234));
+        assert_codec_roundtrip(OutPoint::new(dummy_txid(5), 9));
+        assert_codec_roundtrip(Script::new(vec![0x51, 0x52]));
+        assert_codec_roundtrip(TransactionInput::new(
+            OutPoint::new(dummy_txid(6), 1),
+            Script::new(vec![0x00]),
+            0xFFFFFFFE,
+        ));
+        assert_codec_roundtrip(TransactionOutput::new(42, Script::new(vec![0x6a])));
+        assert_codec_roundtrip(BitcoinTransaction::from_hex(SEGWIT_TX_HEX).unwrap());
+
+        assert_eq!(
+            <OutPoint as Decodable>::decode(&[0u8; 10]),
+            OutPoint::from_bytes(&[0u8; 10])
+        );
+    }
+
+    #[test]
+    fn test_decode_vec_of_outpoints() {
+        let outpoints = vec![
+            OutPoint::new(dummy_txid(1), 0),
+            OutPoint::new(dummy_txid(2), 7),
+        ];
+        let mut bytes = CompactSize::new(2).to_bytes();
+        for outpoint in &outpoints {
+            bytes.extend_from_slice(&outpoint.to_bytes());
+        }
+        bytes.push(0xFF);
+
+        let (decoded, consumed) = decode_vec::<OutPoint>(&bytes).unwrap();
+        assert_eq!(decoded, outpoints);
+        assert_eq!(consumed, bytes.len() - 1);
+
+        // Three outpoints claimed, only two present
+        bytes[0] = 3;
+        assert_eq!(
+            decode_vec::<OutPoint>(&bytes),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert_eq!(decode_vec::<Script>(&[0x00]), Ok((vec![], 1)));
+    }
+
+    #[test]
+    fn test_fee_calculation() {
+        let tx = TransactionBuilder::new()
+            .add_input(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![]),
+                0xFFFFFFFF,
+            )
+            .add_input(
+                OutPoint::new(dummy_txid(2), 0),
+                Script::new(vec![]),
+                0xFFFFFFFF,
+            )
+            .add_output(70_000, Script::new(vec![0x51]))
+            .add_output(25_000, Script::new(vec![0x52]))
+            .build();
+
+        assert_eq!(tx.fee(&[50_000, 50_000]), Ok(5_000));
+        assert_eq!(tx.fee(&[60_000, 35_000]), Ok(0));
+        assert_eq!(tx.fee(&[50_000, 40_000]), Err(BitcoinError::NegativeFee));
+        assert_eq!(
+            tx.fee(&[100_000]),
+            Err(BitcoinError::AmountCountMismatch {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(tx.fee(&[u64::MAX, 1]), Err(BitcoinError::ValueOverflow));
+    }
+
+    #[test]
+    fn test_same_txid_ignores_witness() {
+        let tx = BitcoinTransaction::from_hex(SEGWIT_TX_HEX).unwrap();
+        let mut malleated = tx.clone();
+        malleated.witnesses[0][1] = vec![0x30, 0x44];
+
+        assert_ne!(tx, malleated);
+        assert_ne!(tx.wtxid(), malleated.wtxid());
+        assert!(tx.same_txid(&malleated));
+        assert_eq!(tx.txid(), malleated.txid());
+
+        let mut stripped = tx.clone();
+        stripped.witnesses.clear();
+        assert!(stripped.same_txid(&tx));
+
+        let mut other = tx.clone();
+        other.lock_time += 1;
+        assert!(!other.same_txid(&tx));
+    }
+
+    #[test]
+    fn test_borrowed_parse_does_not_copy_scripts() {
+        let bytes = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let (tx_ref, consumed) = BitcoinTransaction::from_bytes_borrowed(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+
+        // Every script and witness item points into the source buffer
+        let range = bytes.as_ptr_range();
+        let borrowed = |slice: &[u8]| range.contains(&slice.as_ptr());
+        assert!(
+            tx_ref
+                .inputs
+                .iter()
+                .all(|i| borrowed(i.script_sig.as_bytes()) || i.script_sig.as_bytes().is_empty())
+        );
+        assert!(
+            tx_ref
+                .outputs
+                .iter()
+                .all(|o| borrowed(o.script_pubkey.as_bytes()))
+        );
+        assert!(tx_ref.witnesses.iter().flatten().all(|item| borrowed(item)));
+
+        let owned = BitcoinTransaction::parse_exact(&bytes).unwrap();
+        assert_eq!(tx_ref.to_transaction(), owned);
+
+        let script_ref = tx_ref.outputs[0].script_pubkey;
+        let script = &owned.outputs[0].script_pubkey;
+        assert_eq!(script_ref.to_script(), *script);
+        assert_eq!(script_ref.to_bytes(), script.to_bytes());
+        assert_eq!(script_ref.script_type(), script.script_type());
+    }
+
+    #[test]
+    fn test_is_rbf_signaling() {
+        let tx_with_sequences = |sequences: &[u32]| {
+            let inputs = sequences
+                .iter()
+                .map(|&sequence| {
+                    TransactionInput::new(
+                        OutPoint::new(dummy_txid(1), 0),
+                        Script::new(vec![]),
+                        sequence,
+                    )
+                })
+                .collect();
+            BitcoinTransaction::new(2, inputs, vec![], 0)
+        };
+
+        assert!(tx_with_sequences(&[0]).is_rbf_signaling());
+        assert!(tx_with_sequences(&[0xFFFFFFFD]).is_rbf_signaling());
+        assert!(!tx_with_sequences(&[0xFFFFFFFE]).is_rbf_signaling());
+        assert!(!tx_with_sequences(&[0xFFFFFFFF]).is_rbf_signaling());
+        assert!(tx_with_sequences(&[0xFFFFFFFF, 0xFFFFFFFD]).is_rbf_signaling());
+        assert!(!tx_with_sequences(&[]).is_rbf_signaling());
+    }
+
+    #[test]
+    fn test_lock_time_interpretation() {
+        let mut tx = BitcoinTransaction::new(2, vec![], vec![], 499_999_999);
+        assert!(tx.lock_time_is_height());
+        assert_eq!(tx.lock_time_as_height(), Some(499_999_999));
+        assert_eq!(tx.lock_time_as_timestamp(), None);
+
+        tx.lock_time = LOCK_TIME_THRESHOLD;
+        assert!(!tx.lock_time_is_height());
+        assert_eq!(tx.lock_time_as_height(), None);
+        assert_eq!(tx.lock_time_as_timestamp(), Some(500_000_000));
+
+        tx.lock_time = 0;
+        assert_eq!(tx.lock_time_as_height(), Some(0));
+    }
+
+    #[test]
+    fn test_is_final() {
+        let input = |sequence| {
+            TransactionInput::new(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![]),
+                sequence,
+            )
+        };
+        let mut tx = BitcoinTransaction::new(2, vec![input(0xFFFFFFFF), input(0)], vec![], 0);
+        assert!(tx.is_final());
+
+        tx.lock_time = 800_000;
+        assert!(!tx.is_final());
+
+        tx.inputs[1].sequence = 0xFFFFFFFF;
+        assert!(tx.is_final());
+    }
+
+    #[test]
+    fn test_to_json_verbose_matches_core_layout() {
+        let tx = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        let json = tx.to_json_verbose();
+
+        assert_eq!(
+            json["txid"],
+            "971ed48a62c143bbd9c87f4bafa2ef213cfa106c6e140f111931d0be307468dd"
+        );
+        assert_eq!(json["hash"], json["txid"]);
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["locktime"], 0);
+        assert_eq!(json["size"], tx.size());
+
+        let vin = json["vin"].as_array().unwrap();
+        assert_eq!(vin.len(), 1);
+        assert_eq!(
+            vin[0]["txid"],
+            "8d88f527354fbb06d17dabc973895cf5564fd6e83ecb8fc8b0d803844296710c"
+        );
+        assert_eq!(vin[0]["vout"], 0);
+        assert_eq!(vin[0]["sequence"], 0xFFFFFFFFu32);
+        assert_eq!(
+            vin[0]["scriptSig"]["hex"],
+            hex::encode(&tx.inputs[0].script_sig.bytes)
+        );
+        assert!(vin[0].get("txinwitness").is_none());
+
+        let vout = json["vout"].as_array().unwrap();
+        assert_eq!(vout.len(), 2);
+        assert_eq!(vout[0]["value"], 0.78915);
+        assert_eq!(vout[0]["n"], 0);
+        assert_eq!(
+            vout[0]["scriptPubKey"]["hex"],
+            "76a914ef79e7ee9fff98bcfd08473d2b76b02a48f8c69088ac"
+        );
+        assert_eq!(vout[1]["value"], 0.0);
+        assert_eq!(vout[1]["n"], 1);
+
+        let segwit = BitcoinTransaction::from_hex(SEGWIT_TX_HEX).unwrap();
+        let json = segwit.to_json_verbose();
+        assert_eq!(json["weight"], 2718);
+        assert_eq!(json["vsize"], 680);
+        assert_eq!(json["vin"][0]["txinwitness"].as_array().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_compact_size_encoded_len_and_display() {
+        let cases: [(u64, usize, &str); 4] = [
+            (0xFC, 1, "fc"),
+            (0xFD, 3, "fdfd00"),
+            (0x10000, 5, "fe00000100"),
+            (0x100000000, 9, "ff0000000001000000"),
+        ];
+        for (value, len, hex) in cases {
+            let compact = CompactSize::new(value);
+            assert_eq!(compact.encoded_len(), len);
+            assert_eq!(compact.to_bytes().len(), len);
+            assert_eq!(compact.to_bytes_hex(), hex);
+        }
+
+        assert_eq!(CompactSize::new(0xFD).to_string(), "253 (fdfd00)");
+    }
+
+    #[test]
+    fn test_script_builder_p2pkh() {
+        use rust_week_3_exercises::opcodes::*;
+
+        let hash = hex::decode("ef79e7ee9fff98bcfd08473d2b76b02a48f8c690").unwrap();
+        let script = ScriptBuilder::new()
+            .push_opcode(OP_DUP)
+            .push_opcode(OP_HASH160)
+            .push_slice(&hash)
+            .unwrap()
+            .push_opcode(OP_EQUALVERIFY)
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+
+        assert_eq!(
+            hex::encode(&script.bytes),
+            "76a914ef79e7ee9fff98bcfd08473d2b76b02a48f8c69088ac"
+        );
+        assert_eq!(script.script_type(), ScriptType::P2pkh);
+    }
+
+    #[test]
+    fn test_script_builder_pushes() {
+        let bytes = |builder: ScriptBuilder| builder.into_script().bytes;
+
+        assert_eq!(bytes(ScriptBuilder::new().push_slice(&[]).unwrap()), [0x00]);
+        let pushed = bytes(ScriptBuilder::new().push_slice(&[0xAA; 76]).unwrap());
+        assert_eq!(pushed[..2], [0x4c, 76]);
+        let pushed = bytes(ScriptBuilder::new().push_slice(&[0xAA; 520]).unwrap());
+        assert_eq!(pushed[..3], [0x4d, 0x08, 0x02]);
+        assert_eq!(
+            ScriptBuilder::new().push_slice(&[0xAA; 521]).unwrap_err(),
+            BitcoinError::InvalidFormat
+        );
+
+        assert_eq!(bytes(ScriptBuilder::new().push_int(0)), [0x00]);
+        assert_eq!(bytes(ScriptBuilder::new().push_int(-1)), [0x4f]);
+        assert_eq!(bytes(ScriptBuilder::new().push_int(16)), [0x60]);
+        assert_eq!(bytes(ScriptBuilder::new().push_int(17)), [0x01, 0x11]);
+        assert_eq!(
+            bytes(ScriptBuilder::new().push_int(128)),
+            [0x02, 0x80, 0x00]
+        );
+        assert_eq!(
+            bytes(ScriptBuilder::new().push_int(-128)),
+            [0x02, 0x80, 0x80]
+        );
+        assert_eq!(bytes(ScriptBuilder::new().push_int(-2)), [0x01, 0x82]);
+        // BIP-34 style height push
+        assert_eq!(
+            bytes(ScriptBuilder::new().push_int(840_000)),
+            [0x03, 0x40, 0xd1, 0x0c]
+        );
+    }
+
+    #[test]
+    fn test_witness_accessors_on_p2wpkh_input() {
+        // Signed native P2WPKH example from BIP-143: input 0 is P2PK, input 1 P2WPKH
+        let tx = BitcoinTransaction::from_hex(
+            "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000\
+             494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040\
+             529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc\
+             89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb2060000\
+             00001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde\
+             42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4\
+             542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c21\
+             2a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee\
+             635711000000",
+        )
+        .unwrap();
+
+        assert_eq!(tx.witness_for(0), Some(&[][..]));
+        assert_eq!(tx.witness_item_count(0), 0);
+        assert_eq!(tx.witness_last(0), None);
+
+        let witness = tx.witness_for(1).unwrap();
+        assert_eq!(tx.witness_item_count(1), 2);
+        assert_eq!(witness[0].len(), 71);
+        assert_eq!(witness[0].last(), Some(&(SIGHASH_ALL as u8)));
+        assert_eq!(
+            tx.witness_last(1).map(hex::encode).as_deref(),
+            Some("025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357")
+        );
+
+        assert_eq!(tx.witness_for(2), None);
+        assert_eq!(tx.witness_item_count(2), 0);
+
+        // Legacy transactions have no stored witnesses at all
+        let legacy = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        assert_eq!(legacy.witness_for(0), Some(&[][..]));
+    }
+
+    #[test]
+    fn test_coinbase_detection_and_height() {
+        let coinbase = |script_sig: &str| {
+            TransactionBuilder::new()
+                .add_input(
+                    OutPoint::new([0u8; 32], 0xFFFFFFFF),
+                    Script::new(hex::decode(script_sig).unwrap()),
+                    0xFFFFFFFF,
+                )
+                .add_output(312_500_000, Script::new(vec![0x51]))
+                .build()
+        };
+
+        // Height 840000 followed by a pool tag and extranonce
+        let tx =
+            coinbase("0340d10c192f5669614254432f4d696e6564206279206275657374646f6e672f2cfabe6d6d");
+        assert!(tx.is_coinbase());
+        assert_eq!(tx.coinbase_height(), Some(840_000));
+
+        assert_eq!(coinbase("51").coinbase_height(), Some(1));
+        assert_eq!(coinbase("00").coinbase_height(), Some(0));
+        assert_eq!(coinbase("").coinbase_height(), None);
+        assert_eq!(coinbase("0340d1").coinbase_height(), None);
+        assert_eq!(coinbase("0580").coinbase_height(), None);
+
+        let legacy = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        assert!(!legacy.is_coinbase());
+        assert_eq!(legacy.coinbase_height(), None);
+    }
+
+    #[test]
+    fn test_validate_version() {
+        for version in [1, 2] {
+            let tx = BitcoinTransaction::new(version, vec![], vec![], 0);
+            assert_eq!(tx.validate_version(), Ok(()));
+            assert!(Version::from(version).is_standard());
+        }
+
+        let tx = BitcoinTransaction::new(0, vec![], vec![], 0);
+        assert_eq!(
+            tx.validate_version(),
+            Err(BitcoinError::NonStandardVersion(0))
+        );
+        assert!(!Version(0).is_standard());
+        assert!(!Version(MAX_STANDARD_TX_VERSION + 1).is_standard());
+
+        // Still parses and round-trips
+        let parsed = BitcoinTransaction::parse_exact(&tx.to_bytes()).unwrap();
+        assert_eq!(parsed.version, 0);
+    }
+
+    #[test]
+    fn test_compact_size_u64_conversions() {
+        let compact = CompactSize::from(5u64);
+        assert_eq!(compact, CompactSize::new(5));
+        assert_eq!(compact.as_u64(), 5);
+        assert_eq!(u64::from(compact.clone()), 5);
+
+        assert!(compact == 5u64);
+        assert!(compact != 6u64);
+        assert!(compact < 6u64);
+        assert!(compact >= 5u64);
+        assert!(compact > 4u64);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_from_file_raw_and_hex() {
+        let dir = std::env::temp_dir();
+        let expected = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+
+        let hex_path = dir.join(format!("rust-week-3-tx-{}.hex", std::process::id()));
+        std::fs::write(&hex_path, format!("{}\n", LEGACY_TX_HEX)).unwrap();
+        assert_eq!(BitcoinTransaction::from_file(&hex_path).unwrap(), expected);
+
+        let raw_path = dir.join(format!("rust-week-3-tx-{}.bin", std::process::id()));
+        std::fs::write(&raw_path, expected.to_bytes()).unwrap();
+        assert_eq!(BitcoinTransaction::from_file(&raw_path).unwrap(), expected);
+
+        std::fs::remove_file(&hex_path).unwrap();
+        std::fs::remove_file(&raw_path).unwrap();
+        assert!(matches!(
+            BitcoinTransaction::from_file(&hex_path),
+            Err(BitcoinError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_txid_ct_eq_matches_eq() {
+        let a = Txid(dummy_txid(1));
+        let mut first_byte_differs = a.clone();
+        first_byte_differs.0[0] ^= 0x01;
+        let pairs = [
+            (a.clone(), a.clone()),
+            (a.clone(), Txid(dummy_txid(2))),
+            (a.clone(), first_byte_differs),
+            (Txid([0xFF; 32]), Txid([0xFF; 32])),
+        ];
+        for (x, y) in pairs {
+            assert_eq!(x.ct_eq(&y), x == y);
+            assert_eq!(y.ct_eq(&x), x == y);
+        }
+    }
+
+    #[test]
+    fn test_decode_all_back_to_back() {
+        let mut bytes = hex::decode(LEGACY_TX_HEX).unwrap();
+        let legacy_len = bytes.len();
+        bytes.extend(hex::decode(SEGWIT_TX_HEX).unwrap());
+
+        let txs = BitcoinTransaction::decode_all(&bytes, 2).unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(
+            txs[0].txid_hex(),
+            "971ed48a62c143bbd9c87f4bafa2ef213cfa106c6e140f111931d0be307468dd"
+        );
+        assert_eq!(
+            txs[1].txid_hex(),
+            "9652aa62b0e748caeec40c4cb7bc17c6792435cc3dfe447dd1ca24f912a1c6ec"
+        );
+
+        // Leftover bytes after the first transaction
+        let err = BitcoinTransaction::decode_all(&bytes, 1).unwrap_err();
+        assert_eq!(err.offset(), Some(legacy_len));
+
+        // Runs out before the third transaction
+        let err = BitcoinTransaction::decode_all(&bytes, 3).unwrap_err();
+        assert_eq!(
+            err,
+            BitcoinError::ParseError {
+                offset: bytes.len(),
+                kind: ErrorKind::InsufficientBytes
+            }
+        );
+    }
+
+    #[test]
+    fn test_to_bytes_legacy_strips_witness() {
+        let tx = BitcoinTransaction::from_hex(SEGWIT_TX_HEX).unwrap();
+        let legacy = tx.to_bytes_legacy();
+        assert_eq!(legacy.len(), tx.base_size());
+        assert_ne!(legacy, tx.to_bytes());
+
+        // Without witnesses the wtxid is the hash of exactly these bytes
+        let stripped = BitcoinTransaction::parse_exact(&legacy).unwrap();
+        assert!(stripped.witnesses.is_empty());
+        assert_eq!(stripped.to_bytes(), legacy);
+        assert_eq!(
+            stripped.wtxid_hex(),
+            "9652aa62b0e748caeec40c4cb7bc17c6792435cc3dfe447dd1ca24f912a1c6ec"
+        );
+        assert_eq!(stripped.wtxid(), tx.txid());
+
+        let legacy_tx = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        assert_eq!(legacy_tx.to_bytes_legacy(), legacy_tx.to_bytes());
+    }
+
+    #[test]
+    fn test_remove_and_add_inputs_outputs() {
+        let mut tx = BitcoinTransaction::from_hex(SEGWIT_TX_HEX).unwrap();
+        let original_txid = tx.txid();
+        let second_witness = tx.witnesses[1].clone();
+
+        let removed = tx.remove_input(0).unwrap();
+        assert_eq!(tx.input_count(), 1);
+        assert_eq!(tx.witnesses, vec![second_witness]);
+        assert_eq!(tx.remove_input(5), None);
+        assert_ne!(tx.txid(), original_txid);
+
+        let reparsed = BitcoinTransaction::parse_exact(&tx.to_bytes()).unwrap();
+        assert_eq!(reparsed, tx);
+
+        tx.add_input_mut(removed);
+        assert_eq!(tx.input_count(), 2);
+        assert_eq!(tx.witness_item_count(1), 0);
+        assert_eq!(tx.witnesses.len(), 2);
+
+        let output = tx.remove_output(0).unwrap();
+        assert_eq!(output.value, 99_993_760);
+        assert_eq!(tx.output_count(), 10);
+        assert_eq!(tx.remove_output(10), None);
+        tx.add_output_mut(output.clone());
+        assert_eq!(tx.outputs.last(), Some(&output));
+
+        let reparsed = BitcoinTransaction::parse_exact(&tx.to_bytes()).unwrap();
+        assert_eq!(reparsed, tx);
+    }
+
+    #[test]
+    fn test_compact_size_try_new() {
+        assert_eq!(CompactSize::try_new(1000, 1000), Ok(CompactSize::new(1000)));
+        assert_eq!(CompactSize::try_new(0, 0), Ok(CompactSize::new(0)));
+        assert_eq!(
+            CompactSize::try_new(1001, 1000),
+            Err(BitcoinError::InvalidFormat)
+        );
+        assert!(CompactSize::try_new(u64::MAX, u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn test_default_transaction_roundtrip() {
+        let tx = BitcoinTransaction::default();
+        assert_eq!(tx.version, 2);
+        assert_eq!(hex::encode(tx.to_bytes()), "02000000000000000000");
+        assert_eq!(BitcoinTransaction::parse_exact(&tx.to_bytes()).unwrap(), tx);
+
+        let tx = BitcoinTransaction {
+            version: 1,
+            inputs: vec![TransactionInput::default()],
+            outputs: vec![TransactionOutput::default()],
+            ..Default::default()
+        };
+        assert_eq!(tx.inputs[0].previous_output, OutPoint::new([0u8; 32], 0));
+        assert_eq!(tx.inputs[0].sequence, 0xFFFFFFFF);
+        assert!(tx.inputs[0].script_sig.is_empty());
+        let (parsed, consumed) = BitcoinTransaction::from_bytes(&tx.to_bytes()).unwrap();
+        assert_eq!(parsed, tx);
+        assert_eq!(consumed, tx.size());
+    }
+
+    #[test]
+    fn test_dust_threshold_matches_core_defaults() {
+        let p2pkh = hex::decode("76a914ef79e7ee9fff98bcfd08473d2b76b02a48f8c69088ac").unwrap();
+        let output = TransactionOutput::new(546, Script::new(p2pkh.clone()));
+        assert_eq!(output.dust_threshold(3.0), 546);
+        assert!(!output.is_dust(3.0));
+        assert!(TransactionOutput::new(545, Script::new(p2pkh)).is_dust(3.0));
+
+        let mut p2wpkh = vec![0x00, 0x14];
+        p2wpkh.extend([0xAB; 20]);
+        let output = TransactionOutput::new(293, Script::new(p2wpkh));
+        assert_eq!(output.dust_threshold(3.0), 294);
+        assert!(output.is_dust(3.0));
+
+        let mut p2tr = vec![0x51, 0x20];
+        p2tr.extend([0xAB; 32]);
+        assert_eq!(
+            TransactionOutput::new(0, Script::new(p2tr)).dust_threshold(3.0),
+            330
+        );
+
+        let op_return = TransactionOutput::new(0, Script::new(vec![0x6a, 0x01, 0x00]));
+        assert!(!op_return.is_dust(3.0));
+    }
+
+    #[test]
+    fn test_free_le_readers() {
+        let bytes = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09];
+        assert_eq!(read_u16_le(&bytes, 0), Ok(0x0201));
+        assert_eq!(read_u32_le(&bytes, 1), Ok(0x05040302));
+        assert_eq!(read_u64_le(&bytes, 1), Ok(0x0908070605040302));
+
+        // Truncated reads and offsets past the end
+        assert_eq!(read_u16_le(&bytes, 8), Err(BitcoinError::InsufficientBytes));
+        assert_eq!(read_u32_le(&bytes, 6), Err(BitcoinError::InsufficientBytes));
+        assert_eq!(read_u64_le(&bytes, 2), Err(BitcoinError::InsufficientBytes));
+        assert_eq!(
+            read_u32_le(&bytes, 100),
+            Err(BitcoinError::InsufficientBytes)
+        );
+        assert_eq!(
+            read_u64_le(&bytes, usize::MAX),
+            Err(BitcoinError::InsufficientBytes)
+        );
+        assert_eq!(read_u16_le(&[], 0), Err(BitcoinError::InsufficientBytes));
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    fn test_generated_values_roundtrip() {
+        use rust_week_3_exercises::test_utils::{self, Rng};
+
+        let mut rng = Rng::new(0x5eed);
+        for _ in 0..200 {
+            assert_codec_roundtrip(test_utils::compact_size(&mut rng));
+            assert_codec_roundtrip(test_utils::outpoint(&mut rng));
+            assert_codec_roundtrip(test_utils::transaction(&mut rng));
+        }
+    }
+
+    #[test]
+    fn test_is_witness_only() {
+        assert!(Script::new(vec![]).is_empty());
+        assert!(!Script::new(vec![0x51]).is_empty());
+
+        let native = TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::new(vec![]), 0);
+        assert!(native.is_witness_only());
+
+        // Both inputs of the nested segwit transaction push a redeem script
+        let nested = BitcoinTransaction::from_hex(SEGWIT_TX_HEX).unwrap();
+        assert!(nested.inputs.iter().all(|input| !input.is_witness_only()));
+
+        let legacy = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        assert!(!legacy.inputs[0].is_witness_only());
+    }
+
+    #[test]
+    fn test_merkle_root_block_100000() {
+        let txids: Vec<[u8; 32]> = [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ]
+        .iter()
+        .map(|hex| Txid::from_display_hex(hex).unwrap().0)
+        .collect();
+
+        assert_eq!(
+            Txid(merkle_root(&txids)).to_display_hex(),
+            "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766"
+        );
+        // Odd count duplicates the last txid (cross-checked against rust-bitcoin)
+        assert_eq!(
+            Txid(merkle_root(&txids[..3])).to_display_hex(),
+            "fa435470825de273081dcc706b25514c936fa6dc80ab965ce6970d68ddd0b553"
+        );
+
+        let tx = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        assert_eq!(merkle_root(&[tx.txid()]), tx.txid());
+        assert_eq!(merkle_root(&[]), [0u8; 32]);
+    }
+
+    #[test]
+    fn test_serialized_len_matches_to_bytes() {
+        let mut big_script = BitcoinTransaction::default();
+        big_script.add_output_mut(TransactionOutput::new(1, Script::new(vec![0x51; 300])));
+        big_script.add_input_mut(TransactionInput::default());
+
+        let txs = [
+            BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap(),
+            BitcoinTransaction::from_hex(SEGWIT_TX_HEX).unwrap(),
+            BitcoinTransaction::default(),
+            big_script,
+        ];
+        for tx in txs {
+            assert_eq!(tx.serialized_len(), tx.to_bytes().len());
+            assert_eq!(tx.base_size(), tx.to_bytes_legacy().len());
+        }
+    }
+
+    #[test]
+    fn test_amount_conversions() {
+        assert_eq!(Amount::from_btc(1.0), Ok(Amount::from_sat(100_000_000)));
+        assert_eq!(Amount::from_sat(100_000_000).to_btc(), 1.0);
+        assert_eq!(Amount::from_btc(0.78915).unwrap().to_sat(), 78_915_000);
+        assert_eq!(Amount::from_btc(0.00000001).unwrap().to_sat(), 1);
+
+        assert_eq!(Amount::from_btc(21_000_000.0).unwrap().to_sat(), MAX_MONEY);
+        assert_eq!(
+            Amount::from_btc(21_000_000.00000001),
+            Err(BitcoinError::AmountOutOfRange)
+        );
+        assert_eq!(Amount::from_btc(-1.0), Err(BitcoinError::AmountOutOfRange));
+        assert_eq!(
+            Amount::from_btc(f64::NAN),
+            Err(BitcoinError::AmountOutOfRange)
+        );
+
+        let one = Amount::from_sat(1);
+        assert_eq!(one.checked_add(one), Some(Amount::from_sat(2)));
+        assert_eq!(Amount::from_sat(u64::MAX).checked_add(one), None);
+        assert_eq!(Amount::ZERO.checked_sub(one), None);
+
+        let output = TransactionOutput::new(546, Script::new(vec![]));
+        assert_eq!(output.amount(), Amount::from_sat(546));
+    }
+
+    #[test]
+    fn test_output_addresses() {
+        // Expected addresses cross-checked against rust-bitcoin
+        let address = |script: &str, network| {
+            TransactionOutput::new(0, Script::new(hex::decode(script).unwrap())).address(network)
+        };
+        let cases = [
+            (
+                "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac",
+                "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+            ),
+            (
+                "a914889f8c10ff2bd4bb9dabb68c5c0d700a46925e6c87",
+                "3E9QxALyjYDsYFmE7WaqKQ3DcGpMuGhxFT",
+            ),
+            (
+                "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            ),
+            (
+                "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+                "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3",
+            ),
+            (
+                "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+                "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+            ),
+        ];
+        for (script, expected) in cases {
+            assert_eq!(address(script, Network::Mainnet).as_deref(), Some(expected));
+        }
+
+        let p2pkh = "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac";
+        assert_eq!(
+            address(p2pkh, Network::Testnet).as_deref(),
+            Some("mpXwg4jMtRhuSpVq4xS3HFHmCmWp9NyGKt")
+        );
+        let p2wpkh = "0014751e76e8199196d454941c45d1b3a323f1433bd6";
+        assert_eq!(
+            address(p2wpkh, Network::Regtest).as_deref(),
+            Some("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+        );
+        assert_eq!(
+            address(p2wpkh, Network::Signet).as_deref(),
+            Some("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx")
+        );
+
+        assert_eq!(address("6a0100", Network::Mainnet), None);
+        assert_eq!(address("51", Network::Mainnet), None);
+    }
+
+    #[test]
+    fn test_sort_bip69() {
+        // Txids compare reversed, so a high first internal byte barely counts
+        let mut high_first_byte = [0u8; 32];
+        high_first_byte[0] = 0xFF;
+        let input =
+            |txid, vout| TransactionInput::new(OutPoint::new(txid, vout), Script::new(vec![]), 0);
+
+        let mut tx = TransactionBuilder::new()
+            .add_input(OutPoint::new(dummy_txid(2), 0), Script::new(vec![]), 0)
+            .add_input(OutPoint::new(dummy_txid(1), 1), Script::new(vec![]), 0)
+            .add_input(OutPoint::new(high_first_byte, 0), Script::new(vec![]), 0)
+            .add_input(OutPoint::new(dummy_txid(1), 0), Script::new(vec![]), 0)
+            .add_output(2_000, Script::new(vec![0x51]))
+            .add_output(1_000, Script::new(vec![0x52]))
+            .add_output(1_000, Script::new(vec![0x51]))
+            .build();
+        tx.witnesses = vec![
+            vec![vec![2]],
+            vec![vec![11]],
+            vec![vec![0xFF]],
+            vec![vec![10]],
+        ];
+        assert!(!tx.is_bip69_sorted());
+
+        tx.sort_bip69();
+        assert!(tx.is_bip69_sorted());
+        assert_eq!(
+            tx.inputs,
+            vec![
+                input(high_first_byte, 0),
+                input(dummy_txid(1), 0),
+                input(dummy_txid(1), 1),
+                input(dummy_txid(2), 0),
+            ]
+        );
+        assert_eq!(
+            tx.witnesses,
+            vec![
+                vec![vec![0xFF]],
+                vec![vec![10]],
+                vec![vec![11]],
+                vec![vec![2]]
+            ]
+        );
+        let outputs: Vec<(u64, Vec<u8>)> = tx
+            .outputs
+            .iter()
+            .map(|o| (o.value, o.script_pubkey.bytes.clone()))
+            .collect();
+        assert_eq!(
+            outputs,
+            vec![
+                (1_000, vec![0x51]),
+                (1_000, vec![0x52]),
+                (2_000, vec![0x51])
+            ]
+        );
+
+        assert_eq!(BitcoinTransaction::parse_exact(&tx.to_bytes()).unwrap(), tx);
+    }
+
+    #[test]
+    fn test_try_from_bytes() {
+        let compact: CompactSize = [0xFD, 0x00, 0x01].as_slice().try_into().unwrap();
+        assert_eq!(compact, 256u64);
+
+        let outpoint = OutPoint::new(dummy_txid(7), 3);
+        assert_eq!(OutPoint::try_from(outpoint.to_bytes()), Ok(outpoint));
+
+        let script = Script::new(vec![0x51, 0x52]);
+        assert_eq!(Script::try_from(script.to_bytes().as_slice()), Ok(script));
+
+        let input = TransactionInput::default();
+        assert_eq!(TransactionInput::try_from(input.to_bytes()), Ok(input));
+
+        let bytes = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let tx: BitcoinTransaction = bytes.as_slice().try_into().unwrap();
+        assert_eq!(tx, BitcoinTransaction::from_hex(SEGWIT_TX_HEX).unwrap());
+
+        // Trailing bytes are rejected rather than ignored
+        let mut padded = Script::new(vec![0x51]).to_bytes();
+        padded.push(0x00);
+        assert_eq!(Script::try_from(padded), Err(BitcoinError::InvalidFormat));
+        let mut padded = bytes;
+        padded.push(0x00);
+        assert_eq!(
+            BitcoinTransaction::try_from(padded),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_outpoint_decode_vec() {
+        let first = OutPoint::new(dummy_txid(1), 0);
+        let second = OutPoint::new(dummy_txid(2), 5);
+        let mut bytes = vec![0x02];
+        bytes.extend(first.to_bytes());
+        bytes.extend(second.to_bytes());
+
+        let (outpoints, consumed) = OutPoint::decode_vec(&bytes).unwrap();
+        assert_eq!(outpoints, vec![first, second]);
+        assert_eq!(consumed, 73);
+
+        // A count of three with room for only two
+        bytes[0] = 0x03;
+        assert_eq!(
+            OutPoint::decode_vec(&bytes),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_from_bytes_empty_input() {
+        let empty: &[u8] = &[];
+        assert_eq!(
+            CompactSize::from_bytes(empty),
+            Err(BitcoinError::EmptyInput)
+        );
+        assert_eq!(
+            CompactSize::from_bytes_unchecked(empty),
+            Err(BitcoinError::EmptyInput)
+        );
+        assert_eq!(OutPoint::from_bytes(empty), Err(BitcoinError::EmptyInput));
+        assert_eq!(Script::from_bytes(empty), Err(BitcoinError::EmptyInput));
+        assert_eq!(
+            TransactionInput::from_bytes(empty),
+            Err(BitcoinError::EmptyInput)
+        );
+        assert_eq!(
+            TransactionOutput::from_bytes(empty),
+            Err(BitcoinError::EmptyInput)
+        );
+        assert_eq!(
+            BitcoinTransaction::from_bytes(empty),
+            Err(BitcoinError::EmptyInput)
+        );
+        assert_eq!(
+            BitcoinTransaction::from_bytes_borrowed(empty).unwrap_err(),
+            BitcoinError::EmptyInput
+        );
+        assert_eq!(
+            BitcoinTransaction::from_hex(""),
+            Err(BitcoinError::EmptyInput)
+        );
+        assert_eq!(BitcoinError::EmptyInput.to_string(), "empty input");
+
+        // A short but non-empty buffer is still a truncation
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&[0x01]),
+            Err(BitcoinError::ParseError {
+                offset: 0,
+                kind: ErrorKind::InsufficientBytes
+            })
+        );
+    }
+
+    #[test]
+    fn test_prevouts_and_spends() {
+        let tx = BitcoinTransaction::from_hex(SEGWIT_TX_HEX).unwrap();
+        let prevouts = tx.prevouts();
+        assert_eq!(prevouts.len(), 2);
+        assert_eq!(
+            prevouts[0].txid.to_display_hex(),
+            "6c832e9467da3bbe99bc39fc0fe7e73b7b8cfa907e4afff6a4b62b675af934ff"
+        );
+        assert_eq!(prevouts[0].vout, 0);
+        assert_eq!(prevouts[1].vout, 1);
+
+        assert!(prevouts.iter().all(|outpoint| tx.spends(outpoint)));
+        let mut other_vout = prevouts[0].clone();
+        other_vout.vout = 7;
+        assert!(!tx.spends(&other_vout));
+    }
+
+    #[test]
+    fn test_psbt_roundtrip_with_witness_utxo() {
+        // Unsigned BIP-143 P2WPKH example with a witness UTXO for input 1,
+        // serialized by rust-bitcoin
+        let expected = "70736274ff0100a00100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3\
+            edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287\
+            d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85\
+            c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa81\
+            5988ac11000000000001011f0046c323000000001600141d0f172a0ecb48aee1be1f2687d2963ae33f71\
+            a1000000";
+        let bytes = hex::decode(expected).unwrap();
+
+        let (psbt, consumed) = Psbt::from_bytes(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(psbt.unsigned_tx.input_count(), 2);
+        assert_eq!(psbt.witness_utxo(0), Ok(None));
+        let utxo = psbt.witness_utxo(1).unwrap().unwrap();
+        assert_eq!(utxo.value, 600_000_000);
+        assert_eq!(utxo.script_pubkey.script_type(), ScriptType::P2wpkh);
+        assert_eq!(psbt.to_bytes(), bytes);
+
+        // Building the same PSBT from scratch gives the same bytes
+        let mut built = Psbt::new(psbt.unsigned_tx.clone()).unwrap();
+        built.set_witness_utxo(1, &utxo).unwrap();
+        assert_eq!(built, psbt);
+        assert!(built.set_witness_utxo(2, &utxo).is_err());
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[4] = 0x00;
+        assert_eq!(
+            Psbt::from_bytes(&bad_magic),
+            Err(BitcoinError::ParseError {
+                offset: 5,
+                kind: ErrorKind::InvalidFormat
+            })
+        );
+
+        let signed = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        assert_eq!(Psbt::new(signed), Err(BitcoinError::InvalidFormat));
+    }
+
+    // Small LCG so the fuzz-style tests are reproducible without extra crates
+    fn lcg_bytes(state: &mut u64, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                *state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (*state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_script_from_bytes_never_panics_on_short_buffers() {
+        let mut state = 1;
+        for i in 0..20_000 {
+            let mut bytes = lcg_bytes(&mut state, i % 16);
+            // Bias the prefix towards the multi-byte CompactSize markers
+            if let Some(first) = bytes.first_mut().filter(|_| i % 4 == 0) {
+                *first = 0xFD + (i / 4 % 3) as u8;
+            }
+            match Script::from_bytes(&bytes) {
+                Ok((script, consumed)) => {
+                    assert!(consumed <= bytes.len());
+                    assert_eq!(script.to_bytes(), bytes[..consumed]);
+                }
+                Err(e) => assert!(matches!(
+                    e,
+                    BitcoinError::InsufficientBytes
+                        | BitcoinError::InvalidFormat
+                        | BitcoinError::EmptyInput
+                )),
+            }
+        }
+    }
+
+    #[test]
+    fn test_transaction_parsing_never_panics() {
+        let valid = [
+            hex::decode(LEGACY_TX_HEX).unwrap(),
+            hex::decode(SEGWIT_TX_HEX).unwrap(),
+        ];
+        let mut state = 7;
+        let check = |bytes: &[u8]| {
+            if let Ok((tx, consumed)) = BitcoinTransaction::from_bytes(bytes) {
+                assert!(consumed <= bytes.len());
+                assert_eq!(tx.to_bytes(), bytes[..consumed]);
+            }
+            let _ = BitcoinTransaction::from_bytes_borrowed(bytes);
+            let _ = Psbt::from_bytes(bytes);
+        };
+
+        // Pure noise, mostly rejected within the first few fields
+        for i in 0..1_000_000 {
+            let bytes = lcg_bytes(&mut state, i % 64);
+            check(&bytes);
+        }
+
+        // Valid transactions with a few corrupted bytes and a random length
+        for i in 0..50_000 {
+            let mut bytes = valid[i % 2].clone();
+            let noise = lcg_bytes(&mut state, 8);
+            for pair in noise.chunks(2) {
+                let index = (pair[0] as usize * 7 + i) % bytes.len();
+                bytes[index] = pair[1];
+            }
+            bytes.truncate(bytes.len() - (noise[0] as usize % 16));
+            check(&bytes);
+        }
+
+        // Reader methods with degenerate arguments
+        let mut reader = BitcoinReader::new(&[0x05, 0x00]);
+        assert_eq!(reader.read_count(0), Err(BitcoinError::InvalidFormat));
+        let mut reader = BitcoinReader::new(&[0x01, 0xAA]);
+        assert_eq!(reader.read_count(0), Ok(1));
+    }
+
+    #[test]
+    fn test_sequence_relative_lock_time() {
+        let blocks = Sequence(144);
+        assert!(blocks.is_relative_lock_time());
+        assert_eq!(blocks.relative_blocks(), Some(144));
+        assert_eq!(blocks.relative_seconds(), None);
+
+        // Type bit (22) set: value counts 512-second units
+        let time = Sequence((1 << 22) | 10);
+        assert_eq!(time.relative_blocks(), None);
+        assert_eq!(time.relative_seconds(), Some(5120));
+
+        // Bits outside the type flag and the low 16 bits are ignored
+        assert_eq!(Sequence(0x0001_0000 | 6).relative_blocks(), Some(6));
+
+        for disabled in [Sequence::FINAL, Sequence::ENABLE_RBF, Sequence(1 << 31)] {
+            assert!(!disabled.is_relative_lock_time());
+            assert_eq!(disabled.relative_blocks(), None);
+            assert_eq!(disabled.relative_seconds(), None);
+        }
+
+        let input = TransactionInput::new(OutPoint::default(), Script::default(), 0xFFFFFFFD);
+        assert_eq!(input.sequence_typed(), Sequence::ENABLE_RBF);
+    }
+
+    #[test]
+    fn test_zero_input_marker_disambiguation() {
+        let output = TransactionOutput::new(1000, Script::new(vec![0x51]));
+        let mut out_bytes = output.to_bytes();
+
+        // 00 02: legacy, zero inputs and two outputs
+        let mut legacy = vec![0x01, 0x00, 0x00, 0x00, 0x00, 0x02];
+        legacy.extend_from_slice(&out_bytes);
+        legacy.extend_from_slice(&out_bytes);
+        legacy.extend_from_slice(&[0x00; 4]);
+        let (tx, consumed) = BitcoinTransaction::from_bytes(&legacy).unwrap();
+        assert_eq!(consumed, legacy.len());
+        assert!(tx.inputs.is_empty());
+        assert_eq!(tx.outputs.len(), 2);
+        assert!(tx.witnesses.is_empty());
+        assert_eq!(tx.to_bytes(), legacy);
+
+        // 00 01 00: marker, flag, then zero inputs
+        let mut segwit = vec![0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01];
+        segwit.append(&mut out_bytes);
+        segwit.extend_from_slice(&[0x00; 4]);
+        assert_eq!(
+            BitcoinTransaction::from_bytes(&segwit),
+            Err(BitcoinError::ParseError {
+                offset: 6,
+                kind: ErrorKind::InvalidFormat
+            })
+        );
+    }
+
+    #[test]
+    fn test_total_output_value() {
+        let tx = TransactionBuilder::new()
+            .add_output(70_000, Script::new(vec![0x51]))
+            .add_output(25_000, Script::new(vec![0x52]))
+            .add_output(5_000, Script::new(vec![0x53]))
+            .build();
+        assert_eq!(tx.total_output_value(), Ok(100_000));
+        assert_eq!(BitcoinTransaction::default().total_output_value(), Ok(0));
+
+        let tx = TransactionBuilder::new()
+            .add_output(u64::MAX, Script::new(vec![0x51]))
+            .add_output(1, Script::new(vec![0x52]))
+            .build();
+        assert_eq!(tx.total_output_value(), Err(BitcoinError::ValueOverflow));
+    }
+
+    #[test]
+    fn test_script_display_summary() {
+        let mut bytes = vec![0x00, 0x14];
+        bytes.extend_from_slice(&[0xAB; 20]);
+        let p2wpkh = Script::new(bytes);
+        assert_eq!(p2wpkh.to_string(), "P2WPKH (22 bytes): 0014abababababab...");
+        assert_eq!(
+            format!("{:#}", p2wpkh),
+            format!("OP_0 OP_PUSHBYTES_20 {}", "ab".repeat(20))
+        );
+
+        let short = Script::new(vec![0x51, 0x52]);
+        assert_eq!(short.to_string(), "non-standard (2 bytes): 5152");
+        assert_eq!(format!("{:#}", short), "OP_1 OP_2");
+        assert_eq!(Script::default().to_string(), "non-standard (0 bytes)");
+
+        let tx = BitcoinTransaction::new(1, vec![], vec![TransactionOutput::new(1, p2wpkh)], 0);
+        assert!(
+            tx.to_string()
+                .contains("Script PubKey: P2WPKH (22 bytes): 0014abababababab...")
+        );
+        assert!(format!("{:#}", tx).contains("Script PubKey: OP_0 OP_PUSHBYTES_20"));
+    }
+
+    #[test]
+    fn test_from_bytes_partial_truncated_mid_input() {
+        let input = |n| {
+            TransactionInput::new(
+                OutPoint::new(dummy_txid(n), 0),
+                Script::new(vec![0x51]),
+                0xFFFFFFFF,
+            )
+        };
+        let inputs = (1..=5).map(input).collect();
+        let outputs = vec![TransactionOutput::new(1000, Script::new(vec![0x51]))];
+        let tx = BitcoinTransaction::new(2, inputs, outputs, 0);
+        let bytes = tx.to_bytes();
+
+        // Version, count, three full 42-byte inputs, then half of the fourth
+        let cut = 4 + 1 + 3 * 42 + 20;
+        let (partial, offset) = BitcoinTransaction::from_bytes_partial(&bytes[..cut]);
+        assert_eq!(partial.version, Some(2));
+        assert_eq!(partial.input_count, Some(5));
+        assert_eq!(partial.inputs, tx.inputs[..3]);
+        assert_eq!(partial.output_count, None);
+        assert!(!partial.is_complete());
+        assert_eq!(partial.to_transaction(), None);
+        assert_eq!(offset, 4 + 1 + 3 * 42);
+        assert_eq!(
+            partial.error,
+            Some(BitcoinError::ParseError {
+                offset,
+                kind: ErrorKind::InsufficientBytes
+            })
+        );
+
+        let (partial, offset) = BitcoinTransaction::from_bytes_partial(&bytes);
+        assert_eq!(offset, bytes.len());
+        assert_eq!(partial.error, None);
+        assert_eq!(partial.to_transaction(), Some(tx));
+
+        let (partial, offset) = BitcoinTransaction::from_bytes_partial(&[]);
+        assert_eq!((partial.version, offset), (None, 0));
+        assert_eq!(partial.error, Some(BitcoinError::EmptyInput));
+    }
+
+    fn assert_wire_format<T: WireFormat>(value: &T) {
+        assert_eq!(T::byte_order(), ByteOrder::LittleEndian);
+        assert_eq!(value.wire_size(), value.encode().len());
+    }
+
+    #[test]
+    fn test_wire_format_is_little_endian() {
+        let bytes = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert_wire_format(&tx);
+        assert_wire_format(&tx.inputs[0]);
+        assert_wire_format(&tx.inputs[0].previous_output);
+        assert_wire_format(&tx.outputs[0]);
+        assert_wire_format(&tx.outputs[0].script_pubkey);
+        assert_wire_format(&CompactSize::new(0x1_0000_0000));
+
+        let input = TransactionInput::new(OutPoint::default(), Script::default(), 0x1234_5678);
+        let tx = BitcoinTransaction::new(0x0A0B_0C0D, vec![input], vec![], 0);
+        let bytes = tx.encode();
+        assert_eq!(bytes[..4], [0x0D, 0x0C, 0x0B, 0x0A]);
+        // version, input count, outpoint, empty scriptSig length
+        let sequence_at = 4 + 1 + 36 + 1;
+        assert_eq!(
+            bytes[sequence_at..sequence_at + 4],
+            [0x78, 0x56, 0x34, 0x12]
+        );
+    }
+
+    #[test]
+    fn test_null_outpoint() {
+        let null = OutPoint::null();
+        assert!(null.is_null());
+        assert_eq!(null, OutPoint::COINBASE);
+        assert_eq!(null.txid, Txid::ZERO);
+
+        let mut expected = vec![0u8; 32];
+        expected.extend_from_slice(&[0xFF; 4]);
+        assert_eq!(null.to_bytes(), expected);
+
+        assert!(!OutPoint::default().is_null());
+        assert!(!OutPoint::new([0u8; 32], 0xFFFF_FFFE).is_null());
+    }
+
+    #[test]
+    fn test_has_minimal_pushes() {
+        let data = [0xAA; 5];
+        let minimal = ScriptBuilder::new()
+            .push_slice(&data)
+            .unwrap()
+            .into_script();
+        assert_eq!(minimal.bytes[0], 0x05);
+        assert!(minimal.has_minimal_pushes());
+
+        // Same 5 bytes behind OP_PUSHDATA1 and OP_PUSHDATA2
+        let mut pushdata1 = vec![0x4c, 0x05];
+        pushdata1.extend_from_slice(&data);
+        assert!(!Script::new(pushdata1).has_minimal_pushes());
+        let mut pushdata2 = vec![0x4d, 0x05, 0x00];
+        pushdata2.extend_from_slice(&data);
+        assert!(!Script::new(pushdata2).has_minimal_pushes());
+
+        // Single small numbers must use OP_1..OP_16 / OP_1NEGATE
+        assert!(Script::new(vec![0x60, 0x4f]).has_minimal_pushes());
+        assert!(!Script::new(vec![0x01, 0x10]).has_minimal_pushes());
+        assert!(!Script::new(vec![0x01, 0x81]).has_minimal_pushes());
+        assert!(Script::new(vec![0x01, 0x11]).has_minimal_pushes());
+        assert!(!Script::new(vec![0x4c, 0x00]).has_minimal_pushes());
+
+        let big = ScriptBuilder::new()
+            .push_slice(&[0x01; 300])
+            .unwrap()
+            .into_script();
+        assert!(big.has_minimal_pushes());
+
+        // Truncated push
+        assert!(!Script::new(vec![0x05, 0xAA]).has_minimal_pushes());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_from_async_reader_duplex() {
+        use tokio::io::AsyncWriteExt;
+
+        let segwit = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let legacy = hex::decode(LEGACY_TX_HEX).unwrap();
+        let mut stream = segwit.clone();
+        stream.extend_from_slice(&legacy);
+        stream.extend_from_slice(&legacy[..20]);
+
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let writer = tokio::spawn(async move {
+            // Small writes so fields straddle chunk boundaries
+            for chunk in stream.chunks(7) {
+                client.write_all(chunk).await.unwrap();
+            }
+        });
+
+        let first = BitcoinTransaction::from_async_reader(&mut server).await;
+        assert_eq!(first.unwrap().to_bytes(), segwit);
+        let second = BitcoinTransaction::from_async_reader(&mut server).await;
+        assert_eq!(second.unwrap().to_bytes(), legacy);
+        writer.await.unwrap();
+
+        // The writer is dropped, so the stream ends mid-transaction
+        assert_eq!(
+            BitcoinTransaction::from_async_reader(&mut server).await,
+            Err(BitcoinError::InsufficientBytes)
+        );
+    }
+
+    #[test]
+    fn test_framed_bytes_roundtrip() {
+        let bytes = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        let framed = tx.to_framed_bytes();
+        assert_eq!(framed[..4], FRAME_MAGIC);
+        assert_eq!(framed[4], FRAME_VERSION);
+        assert_eq!(framed[5..9], (bytes.len() as u32).to_le_bytes());
+        assert_eq!(framed[9..], bytes);
+
+        let mut stream = framed.clone();
+        stream.extend_from_slice(&framed);
+        let (decoded, consumed) = BitcoinTransaction::from_framed_bytes(&stream).unwrap();
+        assert_eq!((decoded, consumed), (tx, framed.len()));
+    }
+
+    #[test]
+    fn test_framed_bytes_detects_corruption() {
+        let tx = BitcoinTransaction::new(2, vec![TransactionInput::default()], vec![], 0);
+        let framed = tx.to_framed_bytes();
+        let corrupt = |i: usize, byte: u8| {
+            let mut bytes = framed.clone();
+            bytes[i] = byte;
+            BitcoinTransaction::from_framed_bytes(&bytes)
+        };
+
+        assert_eq!(corrupt(0, b'X'), Err(BitcoinError::InvalidFormat));
+        assert_eq!(corrupt(4, 2), Err(BitcoinError::InvalidFormat));
+        // Length one short: the lock time is cut off at byte 4+1+41+1 of the payload
+        assert_eq!(
+            corrupt(5, framed[5] - 1),
+            Err(BitcoinError::ParseError {
+                offset: 9 + 47,
+                kind: ErrorKind::InsufficientBytes,
+            })
+        );
+        // Input count too large for the payload, reported at its frame offset
+        assert_eq!(
+            corrupt(9 + 4, 5),
+            Err(BitcoinError::ParseError {
+                offset: 9 + 4,
+                kind: ErrorKind::InvalidFormat,
+            })
+        );
+        // Length one long with a spare byte: trailing data after the payload
+        let mut padded = framed.clone();
+        padded[5] += 1;
+        padded.push(0);
+        assert_eq!(
+            BitcoinTransaction::from_framed_bytes(&padded),
+            Err(BitcoinError::ParseError {
+                offset: framed.len(),
+                kind: ErrorKind::InvalidFormat,
+            })
+        );
+        // Length one long: runs past the end of the buffer
+        assert_eq!(
+            corrupt(5, framed[5] + 1),
+            Err(BitcoinError::InsufficientBytes)
+        );
+        // Raw consensus bytes are not a frame
+        assert_eq!(
+            BitcoinTransaction::from_framed_bytes(&tx.to_bytes()),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_script_type_input_weight() {
+        assert_eq!(ScriptType::P2wpkh.input_weight(), 272);
+        assert_eq!(ScriptType::P2pkh.input_weight(), 148 * 4);
+        assert_eq!(ScriptType::P2tr.input_weight(), 230);
+        assert!(ScriptType::P2sh.input_weight() > ScriptType::P2wpkh.input_weight());
+        assert_eq!(ScriptType::OpReturn.input_weight(), 0);
+    }
+
+    #[test]
+    fn test_matches_template_p2pkh() {
+        use ScriptPattern::{AnyPush, Op};
+        let p2pkh = [
+            Op(opcodes::OP_DUP),
+            Op(opcodes::OP_HASH160),
+            AnyPush,
+            Op(opcodes::OP_EQUALVERIFY),
+            Op(opcodes::OP_CHECKSIG),
+        ];
+
+        let hash = [0x5A; 20];
+        let mut bytes = vec![0x76, 0xa9, 0x14];
+        bytes.extend_from_slice(&hash);
+        bytes.extend_from_slice(&[0x88, 0xac]);
+        let script = Script::new(bytes);
+        assert_eq!(script.matches_template(&p2pkh), Some(vec![hash.to_vec()]));
+
+        // Too short, too long, wrong opcode
+        assert_eq!(script.matches_template(&p2pkh[..4]), None);
+        assert_eq!(
+            script.matches_template(&[&p2pkh[..], &[AnyPush]].concat()),
+            None
+        );
+        let p2wpkh = Script::new([&[0x00, 0x14][..], &hash].concat());
+        assert_eq!(p2wpkh.matches_template(&p2pkh), None);
+        assert_eq!(
+            p2wpkh.matches_template(&[Op(opcodes::OP_0), AnyPush]),
+            Some(vec![hash.to_vec()])
+        );
+    }
+
+    #[test]
+    fn test_compact_size_len_from_prefix() {
+        for prefix in 0x00..=0xFC {
+            assert_eq!(CompactSize::len_from_prefix(prefix), 1);
+        }
+        assert_eq!(CompactSize::len_from_prefix(0xFD), 3);
+        assert_eq!(CompactSize::len_from_prefix(0xFE), 5);
+        assert_eq!(CompactSize::len_from_prefix(0xFF), 9);
+
+        for value in [0, 0xFC, 0xFD, 0xFFFF, 0x10000, 0xFFFF_FFFF, u64::MAX] {
+            let bytes = CompactSize::new(value).to_bytes();
+            assert_eq!(CompactSize::len_from_prefix(bytes[0]), bytes.len());
+        }
+    }
+
+    #[test]
+    fn test_with_input_script() {
+        let tx = TransactionBuilder::new()
+            .add_input(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::new(vec![0x51]),
+                0xFFFFFFFF,
+            )
+            .add_input(
+                OutPoint::new(dummy_txid(2), 1),
+                Script::new(vec![0x52]),
+                0xFFFFFFFE,
+            )
+            .add_output(1000, Script::new(vec![0x53]))
+            .build();
+
+        let signed = tx
+            .with_input_script(1, Script::new(vec![0xAA, 0xBB]))
+            .unwrap();
+        assert_eq!(signed.inputs[0], tx.inputs[0]);
+        assert_eq!(signed.inputs[1].script_sig, Script::new(vec![0xAA, 0xBB]));
+        assert_eq!(
+            signed.inputs[1].previous_output,
+            tx.inputs[1].previous_output
+        );
+        assert_eq!(signed.inputs[1].sequence, tx.inputs[1].sequence);
+        assert_eq!(signed.outputs, tx.outputs);
+        assert_eq!(tx.inputs[1].script_sig, Script::new(vec![0x52]));
+
+        let mut tx = tx;
+        assert_eq!(
+            tx.set_input_script(2, Script::default()),
+            Err(BitcoinError::InputIndexOutOfRange { index: 2, count: 2 })
+        );
+        tx.set_input_script(0, Script::default()).unwrap();
+        assert!(tx.inputs[0].script_sig.is_empty());
+    }
+
+    #[test]
+    fn test_from_bytes_with_mode() {
+        // Legacy with zero inputs and one output: `00 01` after the version
+        let output = TransactionOutput::new(5000, Script::new(vec![0x51]));
+        let mut bytes = vec![0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+        bytes.extend_from_slice(&output.to_bytes());
+        bytes.extend_from_slice(&[0x00; 4]);
+
+        let (tx, consumed) =
+            BitcoinTransaction::from_bytes_with_mode(&bytes, SerializeMode::Legacy).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert!(tx.inputs.is_empty());
+        assert_eq!(tx.outputs, vec![output]);
+
+        assert!(BitcoinTransaction::from_bytes_with_mode(&bytes, SerializeMode::Auto).is_err());
+        assert!(BitcoinTransaction::from_bytes_with_mode(&bytes, SerializeMode::Segwit).is_err());
+
+        let segwit = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let (auto, _) =
+            BitcoinTransaction::from_bytes_with_mode(&segwit, SerializeMode::Auto).unwrap();
+        let (forced, _) =
+            BitcoinTransaction::from_bytes_with_mode(&segwit, SerializeMode::Segwit).unwrap();
+        assert_eq!(auto, forced);
+        assert_eq!(auto, BitcoinTransaction::from_bytes(&segwit).unwrap().0);
+
+        let legacy = hex::decode(LEGACY_TX_HEX).unwrap();
+        assert_eq!(
+            BitcoinTransaction::from_bytes_with_mode(&legacy, SerializeMode::Segwit),
+            Err(BitcoinError::ParseError {
+                offset: 4,
+                kind: ErrorKind::InvalidFormat
+            })
+        );
+        assert!(BitcoinTransaction::from_bytes_with_mode(&legacy, SerializeMode::Legacy).is_ok());
+    }
+
+    #[test]
+    fn test_signing_context() {
+        // The BIP-143 native P2WPKH example
+        let tx = BitcoinTransaction::from_hex(
+            "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000\
+             eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ff\
+             ffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d\
+             000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000",
+        )
+        .unwrap();
+        let bytes = tx.to_bytes();
+        let script_code =
+            Script::new(hex::decode("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap());
+
+        let ctx = SigningContext::new(&tx, vec![625_000_000, 600_000_000]).unwrap();
+        assert_eq!(ctx.prevout_value(1), Some(600_000_000));
+        assert_eq!(
+            ctx.segwit_sighash(1, &script_code, SighashType::All),
+            tx.segwit_sighash(1, &script_code, 600_000_000, SighashType::All)
+        );
+        assert_eq!(ctx.fee(), tx.fee(&[625_000_000, 600_000_000]));
+        assert_eq!(
+            ctx.segwit_sighash(2, &script_code, SighashType::All),
+            Err(BitcoinError::InputIndexOutOfRange { index: 2, count: 2 })
+        );
+
+        // Amounts are not part of the transaction or its encoding
+        assert_eq!(ctx.transaction().to_bytes(), bytes);
+        assert_eq!(BitcoinTransaction::parse_exact(&bytes).unwrap(), tx);
+
+        assert_eq!(
+            SigningContext::new(&tx, vec![1]).unwrap_err(),
+            BitcoinError::AmountCountMismatch {
+                expected: 2,
+                actual: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_estimated_vsize_p2wpkh_one_in_two_out() {
+        let p2wpkh = Script::new([&[0x00, 0x14][..], &[0x11; 20]].concat());
+        let tx = TransactionBuilder::new()
+            .add_input(
+                OutPoint::new(dummy_txid(1), 0),
+                Script::default(),
+                0xFFFFFFFD,
+            )
+            .add_output(50_000, p2wpkh.clone())
+            .add_output(40_000, p2wpkh)
+            .build();
+
+        // 72 non-input bytes, 2 WU marker and flag, 272 WU input: 562 WU
+        assert_eq!(tx.estimated_vsize(&[ScriptType::P2wpkh]), Ok(141));
+        // Legacy-only: no marker, four times the 148-byte input
+        assert_eq!(tx.estimated_vsize(&[ScriptType::P2pkh]), Ok(72 + 148));
+
+        assert_eq!(
+            tx.estimated_vsize(&[]),
+            Err(BitcoinError::ScriptTypeCountMismatch {
+                expected: 1,
+                actual: 0
+            })
+        );
+        assert_eq!(
+            tx.estimated_vsize(&[ScriptType::NonStandard]),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_streamed_txid_matches_serialized_hash() {
+        use sha2::{Digest, Sha256};
+        let sha256d = |bytes: &[u8]| -> [u8; 32] { Sha256::digest(Sha256::digest(bytes)).into() };
+
+        // Roughly 100KB: 2000 inputs with 10-byte scripts and witnesses
+        let mut state = 7;
+        let inputs = (0..2000)
+            .map(|i| {
+                TransactionInput::new(
+                    OutPoint::new(dummy_txid(i as u8), i),
+                    Script::new(lcg_bytes(&mut state, 10)),
+                    0xFFFFFFFF,
+                )
+            })
+            .collect();
+        let outputs = vec![TransactionOutput::new(1, Script::new(vec![0x51]))];
+        let mut tx = BitcoinTransaction::new(2, inputs, outputs, 0);
+        assert_eq!(tx.txid(), sha256d(&tx.to_bytes()));
+        assert_eq!(tx.wtxid(), tx.txid());
+
+        tx.witnesses = (0..2000).map(|_| vec![lcg_bytes(&mut state, 10)]).collect();
+        assert!(tx.size() > 100_000);
+        assert_eq!(tx.txid(), sha256d(&tx.to_bytes_legacy()));
+        assert_eq!(tx.wtxid(), sha256d(&tx.to_bytes()));
+    }
+
+    #[test]
+    fn test_network_magic_and_version_bytes() {
+        assert_eq!(u32::from_be_bytes(Network::Mainnet.magic()), 0xF9BEB4D9);
+        assert_eq!(Network::Testnet.magic(), [0x0b, 0x11, 0x09, 0x07]);
+        assert_eq!(Network::Regtest.magic(), [0xfa, 0xbf, 0xb5, 0xda]);
+        assert_eq!(Network::Signet.magic(), [0x0a, 0x03, 0xcf, 0x40]);
+
+        assert_eq!(Network::Mainnet.address_version_bytes(), (0x00, 0x05));
+        for network in [Network::Testnet, Network::Regtest, Network::Signet] {
+            assert_eq!(network.address_version_bytes(), (0x6f, 0xc4));
+        }
+    }
+
+    #[test]
+    fn test_transaction_diff() {
+        let bytes = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        assert!(tx.diff(&tx.clone()).is_empty());
+
+        let mut other = tx.clone();
+        other.outputs[1].value += 1;
+        let diffs = tx.diff(&other);
+        assert_eq!(
+            diffs,
+            vec![FieldDiff::Value {
+                index: 1,
+                left: tx.outputs[1].value,
+                right: tx.outputs[1].value + 1
+            }]
+        );
+        assert_eq!(diffs[0].path(), "outputs[1].value");
+        assert_eq!(diffs[0].to_string(), "outputs[1].value: 998403 != 998404");
+
+        other.lock_time = 7;
+        other.witnesses[0].clear();
+        let paths: Vec<String> = tx.diff(&other).iter().map(FieldDiff::path).collect();
+        assert_eq!(paths, ["outputs[1].value", "witnesses[0]", "lock_time"]);
+    }
+
+    #[test]
+    fn test_script_into_pushes() {
+        let bytes = hex::decode(LEGACY_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        let pushes = tx.inputs[0].script_sig.into_pushes(false).unwrap();
+        assert_eq!(pushes.len(), 2);
+        let (signature, pubkey) = (&pushes[0], &pushes[1]);
+        assert_eq!(signature.len(), 71);
+        assert_eq!(signature[0], 0x30);
+        assert_eq!(signature.last(), Some(&(SIGHASH_ALL as u8)));
+        assert_eq!(
+            hex::encode(pubkey),
+            "024d89b5a13d6521388969209df27a8469bd565aff10e8d42cef931fad5121bfb8"
+        );
+
+        // PUSHDATA1 and small-number opcodes
+        let script = Script::new(vec![0x4c, 0x02, 0xAA, 0xBB, 0x00, 0x52, 0x4f]);
+        assert_eq!(
+            script.into_pushes(false),
+            Ok(vec![vec![0xAA, 0xBB], vec![], vec![2], vec![0x81]])
+        );
+
+        let with_op = Script::new(vec![0x01, 0xAA, 0xac]);
+        assert_eq!(with_op.into_pushes(false), Err(BitcoinError::InvalidFormat));
+        assert_eq!(with_op.into_pushes(true), Ok(vec![vec![0xAA]]));
+        assert_eq!(
+            Script::new(vec![0x4c, 0x05, 0xAA]).into_pushes(true),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_compact_size_from_len() {
+        assert_eq!(CompactSize::from_usize(0xFD), CompactSize::new(0xFD));
+        assert_eq!(CompactSize::from_len(&[0u8; 3]).value, 3);
+
+        // Zero-sized elements, so a 2^32-element Vec allocates nothing
+        let huge = vec![(); 0x1_0000_0000];
+        let prefix = CompactSize::from_len(&huge);
+        assert_eq!(prefix.value, 0x1_0000_0000);
+        assert_eq!(prefix.encoded_len(), 9);
+        assert_eq!(CompactSize::from_usize(usize::MAX).value, usize::MAX as u64);
+    }
+
+    #[test]
+    fn test_strip_witnesses_preserves_txid() {
+        let bytes = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let (mut tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        let txid = tx.txid();
+        assert_ne!(tx.wtxid(), txid);
+
+        tx.strip_witnesses();
+        assert!(!tx.is_segwit());
+        assert_eq!(tx.txid(), txid);
+        assert_eq!(tx.wtxid(), txid);
+        assert_eq!(tx.to_bytes(), tx.to_bytes_legacy());
+        assert_eq!(tx.size(), tx.base_size());
+        assert_eq!(BitcoinTransaction::parse_exact(&tx.to_bytes()).unwrap(), tx);
+    }
+
+    #[test]
+    fn test_from_bytes_with_limits() {
+        let bytes = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        let defaults = Limits::default();
+        assert_eq!(defaults.max_script_len, MAX_SCRIPT_SIZE);
+        assert_eq!(defaults.max_tx_size, MAX_TX_SIZE);
+        assert_eq!(
+            BitcoinTransaction::from_bytes_with_limits(&bytes, &defaults),
+            Ok((tx.clone(), bytes.len()))
+        );
+
+        let rejects = |limits: Limits| {
+            matches!(
+                BitcoinTransaction::from_bytes_with_limits(&bytes, &limits),
+                Err(BitcoinError::ParseError {
+                    kind: ErrorKind::InvalidFormat,
+                    ..
+                })
+            )
+        };
+        assert!(rejects(Limits {
+            max_inputs: 1,
+            ..defaults
+        }));
+        assert!(rejects(Limits {
+            max_outputs: tx.outputs.len() - 1,
+            ..defaults
+        }));
+        assert!(rejects(Limits {
+            max_script_len: 30,
+            ..defaults
+        }));
+        // The reader stops at the size cap, here inside the lock time
+        assert_eq!(
+            BitcoinTransaction::from_bytes_with_limits(
+                &bytes,
+                &Limits {
+                    max_tx_size: bytes.len() - 1,
+                    ..defaults
+                }
+            ),
+            Err(BitcoinError::ParseError {
+                offset: bytes.len() - 4,
+                kind: ErrorKind::InsufficientBytes,
+            })
+        );
+        // Counts and lengths are checked against the window, not the buffer
+        assert!(rejects(Limits {
+            max_tx_size: 200,
+            ..defaults
+        }));
+        assert!(!rejects(Limits {
+            max_inputs: tx.inputs.len(),
+            max_outputs: tx.outputs.len(),
+            max_script_len: 35,
+            max_tx_size: bytes.len(),
+        }));
+    }
+
+    #[test]
+    fn test_field_offsets() {
+        let bytes = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&bytes).unwrap();
+        let layout = tx.field_offsets();
+
+        assert_eq!(layout.version, 0..4);
+        assert_eq!(layout.marker, Some(4..6));
+        assert_eq!(bytes[layout.input_count.clone()], [0x02]);
+        for (range, input) in layout.inputs.iter().zip(&tx.inputs) {
+            assert_eq!(bytes[range.clone()], input.to_bytes());
+        }
+        for (range, output) in layout.outputs.iter().zip(&tx.outputs) {
+            assert_eq!(bytes[range.clone()], output.to_bytes());
+        }
+        assert_eq!(layout.witnesses.len(), tx.inputs.len());
+        assert_eq!(
+            layout.witnesses[0].start,
+            layout.outputs.last().unwrap().end
+        );
+        assert_eq!(layout.lock_time, bytes.len() - 4..bytes.len());
+
+        let legacy = hex::decode(LEGACY_TX_HEX).unwrap();
+        let (tx, _) = BitcoinTransaction::from_bytes(&legacy).unwrap();
+        let layout = tx.field_offsets();
+        assert_eq!((layout.version, layout.marker), (0..4, None));
+        assert_eq!(layout.input_count, 4..5);
+        assert!(layout.witnesses.is_empty());
+        assert_eq!(layout.lock_time.end, legacy.len());
+    }
+
+    #[test]
+    fn test_from_bytes_into_reuses_allocations() {
+        let segwit = hex::decode(SEGWIT_TX_HEX).unwrap();
+        let legacy = hex::decode(LEGACY_TX_HEX).unwrap();
+        let mut out = BitcoinTransaction::default();
+
+        assert_eq!(
+            BitcoinTransaction::from_bytes_into(&segwit, &mut out),
+            Ok(segwit.len())
+        );
+        assert_eq!(out, BitcoinTransaction::from_bytes(&segwit).unwrap().0);
+        let inputs_ptr = out.inputs.as_ptr();
+        let outputs_ptr = out.outputs.as_ptr();
+        let script_ptr = out.outputs[0].script_pubkey.bytes.as_ptr();
+
+        // The legacy transaction is smaller in every dimension
+        assert_eq!(
+            BitcoinTransaction::from_bytes_into(&legacy, &mut out),
+            Ok(legacy.len())
+        );
+        assert_eq!(out, BitcoinTransaction::from_bytes(&legacy).unwrap().0);
+        assert!(out.witnesses.is_empty());
+        assert_eq!(out.inputs.as_ptr(), inputs_ptr);
+        assert_eq!(out.outputs.as_ptr(), outputs_ptr);
+        assert_eq!(out.outputs[0].script_pubkey.bytes.as_ptr(), script_ptr);
+
+        assert_eq!(
+            BitcoinTransaction::from_bytes_into(&segwit, &mut out),
+            Ok(segwit.len())
+        );
+        assert_eq!(out.to_bytes(), segwit);
+        assert!(BitcoinTransaction::from_bytes_into(&segwit[..50], &mut out).is_err());
+    }
+
+    #[test]
+    fn test_sighash_type_from_u32() {
+        let all_acp = SighashType::from_u32(0x81).unwrap();
+        assert_eq!(all_acp, SighashType::AllPlusAnyoneCanPay);
+        assert!(all_acp.anyone_can_pay());
+        assert_eq!(all_acp.base(), SighashType::All);
+        assert_eq!(all_acp.to_u32(), 0x81);
+
+        let single = SighashType::from_u32(0x03).unwrap();
+        assert_eq!(single, SighashType::Single);
+        assert!(!single.anyone_can_pay());
+        assert_eq!(single.to_u32(), 0x03);
+
+        for invalid in [0x00, 0x04, 0x80, 0x84, 0x41, 0x101] {
+            assert_eq!(
+                SighashType::from_u32(invalid),
+                Err(BitcoinError::InvalidFormat)
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_sanity_duplicate_inputs() {
+        let input = |vout| {
+            TransactionInput::new(OutPoint::new(dummy_txid(1), vout), Script::new(vec![]), 0)
+        };
+        let output = TransactionOutput::new(1_000, Script::new(vec![0x51]));
+        let tx = BitcoinTransaction::new(1, vec![input(0), input(1)], vec![output.clone()], 0);
+        assert_eq!(tx.check_sanity(), Ok(()));
+
+        let tx = BitcoinTransaction::new(
+            1,
+            vec![input(0), input(1), input(0)],
+            vec![output.clone()],
+            0,
+        );
+        assert_eq!(
+            tx.check_sanity(),
+            Err(BitcoinError::DuplicateInput { index: 2 })
+        );
+
+        let tx = BitcoinTransaction::new(1, vec![], vec![output.clone()], 0);
+        assert_eq!(tx.check_sanity(), Err(BitcoinError::NoInputs));
+
+        let coinbase = |len| {
+            let input = TransactionInput::new(OutPoint::COINBASE, Script::new(vec![0; len]), 0);
+            BitcoinTransaction::new(1, vec![input], vec![output.clone()], 0)
+        };
+        assert_eq!(coinbase(2).check_sanity(), Ok(()));
+        assert_eq!(
+            coinbase(101).check_sanity(),
+            Err(BitcoinError::CoinbaseScriptLength(101))
+        );
+
+        let tx = BitcoinTransaction::new(
+            1,
+            vec![
+                input(0),
+                TransactionInput::new(OutPoint::COINBASE, Script::new(vec![]), 0),
+            ],
+            vec![output],
+            0,
+        );
+        assert_eq!(
+            tx.check_sanity(),
+            Err(BitcoinError::NullPrevout { index: 1 })
+        );
+    }
+
+    #[test]
+    fn test_check_sanity_value_overflow() {
+        let input = TransactionInput::new(OutPoint::new(dummy_txid(1), 0), Script::new(vec![]), 0);
+        let output = |value| TransactionOutput::new(value, Script::new(vec![0x51]));
+
+        let tx = BitcoinTransaction::new(1, vec![input.clone()], vec![output(MAX_MONEY)], 0);
+        assert_eq!(tx.check_sanity(), Ok(()));
+
+        let tx = BitcoinTransaction::new(1, vec![input.clone()], vec![output(MAX_MONEY + 1)], 0);
+        assert_eq!(tx.check_sanity(), Err(BitcoinError::AmountOutOfRange));
+
+        let tx = BitcoinTransaction::new(
+            1,
+            vec![input.clone()],
+            vec![output(MAX_MONEY), output(1)],
+            0,
+        );
+        assert_eq!(tx.check_sanity(), Err(BitcoinError::ValueOverflow));
+
+        let tx = BitcoinTransaction::new(1, vec![input], vec![], 0);
+        assert_eq!(tx.check_sanity(), Err(BitcoinError::NoOutputs));
+    }
+
+    #[test]
+    fn test_has_duplicate_inputs() {
+        let input = |vout| {
+            TransactionInput::new(OutPoint::new(dummy_txid(7), vout), Script::new(vec![]), 0)
+        };
+        let output = TransactionOutput::new(1_000, Script::new(vec![0x51]));
+
+        let tx = BitcoinTransaction::new(1, vec![input(0), input(1)], vec![output.clone()], 0);
+        assert!(!tx.has_duplicate_inputs());
+
+        let tx = BitcoinTransaction::new(1, vec![input(3), input(1), input(3)], vec![output], 0);
+        assert!(tx.has_duplicate_inputs());
+    }
+
+    #[test]
+    fn test_standard_script_constructors() {
+        let hash20 = [0x11u8; 20];
+        let hash32 = [0x22u8; 32];
+        let cases = [
+            (
+                Script::p2pkh(&hash20),
+                format!("76a914{}88ac", "11".repeat(20)),
+                ScriptType::P2pkh,
+            ),
+            (
+                Script::p2sh(&hash20),
+                format!("a914{}87", "11".repeat(20)),
+                ScriptType::P2sh,
+            ),
+            (
+                Script::p2wpkh(&hash20),
+                format!("0014{}", "11".repeat(20)),
+                ScriptType::P2wpkh,
+            ),
+            (
+                Script::p2wsh(&hash32),
+                format!("0020{}", "22".repeat(32)),
+                ScriptType::P2wsh,
+            ),
+            (
+                Script::p2tr(&hash32),
+                format!("5120{}", "22".repeat(32)),
+                ScriptType::P2tr,
+            ),
+        ];
+        for (script, expected_hex, expected_type) in cases {
+            assert_eq!(hex::encode(&script.bytes), expected_hex);
+            assert_eq!(script.script_type(), expected_type);
+        }
+    }
+
+    #[test]
+    fn test_builder_running_size() {
+        let mut builder = TransactionBuilder::new();
+        assert_eq!(
+            builder.running_size(),
+            builder.clone().build().serialized_len()
+        );
+
+        for i in 0..300u32 {
+            builder = builder
+                .add_input(
+                    OutPoint::new(dummy_txid(1), i),
+                    Script::new(vec![0x51; i as usize % 3]),
+                    0,
+                )
+                .add_output(1_000, Script::p2wpkh(&[0x33; 20]));
+            assert_eq!(
+                builder.running_size(),
+                builder.clone().build().serialized_len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_compact_size_u32_u64_boundary() {
+        let tests = vec![
+            (0xFFFFFFFFu64, vec![0xFE, 0xFF, 0xFF, 0xFF, 0xFF]),
+            (
+                0x100000000u64,
+                vec![0xFF, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00],
+            ),
+            (
+                u64::MAX,
+                vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            ),
+        ];
+
+        for (value, bytes) in tests {
+            let cs = CompactSize::new(value);
+            assert_eq!(cs.to_bytes(), bytes);
+            assert_eq!(cs.encoded_len(), bytes.len());
+            assert_eq!(CompactSize::len_from_prefix(bytes[0]), bytes.len());
+            let (decoded, consumed) = CompactSize::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded, cs);
+            assert_eq!(consumed, bytes.len());
+        }
+    }
+
+    #[test]
+    fn test_weight_breakdown_sums_to_weight() {
+        for tx_hex in [LEGACY_TX_HEX, SEGWIT_TX_HEX] {
+            let tx = BitcoinTransaction::from_hex(tx_hex).unwrap();
+            let breakdown = tx.weight_breakdown();
+            assert_eq!(breakdown.base + breakdown.witness, tx.weight());
+            assert_eq!(breakdown.inputs.len(), tx.inputs.len());
+            assert_eq!(breakdown.outputs.len(), tx.outputs.len());
+
+            let itemized: usize =
+                breakdown.inputs.iter().sum::<usize>() + breakdown.outputs.iter().sum::<usize>();
+            assert_eq!(itemized + breakdown.overhead(), tx.weight());
+            // Version, lock time and one-byte counts, plus marker and flag for segwit
+            let marker = if tx.is_segwit() { 2 } else { 0 };
+            assert_eq!(breakdown.overhead(), (4 + 1 + 1 + 4) * 4 + marker);
+        }
+
+        let legacy = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        assert_eq!(legacy.weight_breakdown().witness, 0);
+    }
+
+    #[cfg(feature = "rust-bitcoin")]
+    #[test]
+    fn test_rust_bitcoin_roundtrip() {
+        use bitcoin::hashes::Hash;
+
+        for tx_hex in [LEGACY_TX_HEX, SEGWIT_TX_HEX] {
+            let tx = BitcoinTransaction::from_hex(tx_hex).unwrap();
+            let theirs = bitcoin::Transaction::try_from(tx.clone()).unwrap();
+            assert_eq!(bitcoin::consensus::serialize(&theirs), tx.to_bytes());
+            assert_eq!(theirs.compute_txid().to_byte_array(), tx.txid());
+
+            let back = BitcoinTransaction::from(theirs);
+            assert_eq!(back, tx);
+        }
+
+        let outpoint = OutPoint::new(dummy_txid(9), 3);
+        let theirs = bitcoin::OutPoint::from(outpoint.clone());
+        assert_eq!(bitcoin::consensus::serialize(&theirs), outpoint.to_bytes());
+        assert_eq!(OutPoint::from(theirs), outpoint);
+
+        let script = Script::p2wpkh(&[0x44; 20]);
+        let theirs = bitcoin::ScriptBuf::from(script.clone());
+        assert!(theirs.is_p2wpkh());
+        assert_eq!(Script::from(theirs), script);
+
+        let mut tx = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        tx.witnesses = vec![vec![], vec![]];
+        assert_eq!(
+            bitcoin::Transaction::try_from(tx),
+            Err(BitcoinError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_deserialize_validated() {
+        #[derive(serde::Deserialize)]
+        struct Config {
+            #[serde(deserialize_with = "deserialize_validated")]
+            tx: BitcoinTransaction,
+        }
+
+        let tx = BitcoinTransaction::from_hex(LEGACY_TX_HEX).unwrap();
+        let json = format!("{{\"tx\": {}}}", serde_json::to_string(&tx).unwrap());
+        let config: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.tx, tx);
+
+        let empty = BitcoinTransaction::new(1, vec![], tx.outputs.clone(), 0);
+        let json = format!("{{\"tx\": {}}}", serde_json::to_string(&empty).unwrap());
+        let err = serde_json::from_str::<Config>(&json).err().unwrap();
+        assert!(err.is_data());
+        assert!(err.to_string().contains("transaction has no inputs"));
+    }
+
+    #[test]
+    fn test_display_truncates_many_inputs() {
+        let mut builder = TransactionBuilder::new().add_output(1_000, Script::new(vec![0x51]));
+        for vout in 0..50 {
+            builder = builder.add_input(OutPoint::new(dummy_txid(1), vout), Script::new(vec![]), 0);
+        }
+        let tx = builder.build();
+
+        let output = tx.to_string();
+        assert!(output.contains("Inputs (50)"));
+        assert!(output.contains("    Input 9:"));
+        assert!(!output.contains("    Input 10:"));
+        assert!(output.contains("    ... and 40 more\n  Outputs (1)"));
+        assert!(output.ends_with("Lock Time: 0"));
+
+        let limited = tx.display_with_limit(45).to_string();
+        assert!(limited.contains("    ... and 5 more"));
+
+        let full = format!("{:#}", tx);
+        assert!(full.contains("    Input 49:"));
+        assert!(!full.contains("more"));
+    }
+
+    #[test]
+    fn test_framed_bytes_roundtrip_beyond_default_limits() {
+        for script_len in [MAX_SCRIPT_SIZE + 1, MAX_TX_SIZE] {
+            let output = TransactionOutput::new(0, Script::new(vec![0x6a; script_len]));
+            let tx = BitcoinTransaction::new(2, vec![TransactionInput::default()], vec![output], 0);
+            assert!(BitcoinTransaction::from_bytes(&tx.to_bytes()).is_err());
+
+            let framed = tx.to_framed_bytes();
+            assert_eq!(
+                BitcoinTransaction::from_framed_bytes(&framed),
+                Ok((tx, framed.len()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_psbt_zero_input_one_output_roundtrip() {
+        let tx = TransactionBuilder::new()
+            .add_output(5_000, Script::new(vec![0x51]))
+            .build();
+        // Legacy bytes after the version read like a segwit marker and flag
+        assert_eq!(tx.to_bytes()[4..6], [0x00, 0x01]);
+
+        let psbt = Psbt::new(tx.clone()).unwrap();
+        let bytes = psbt.to_bytes();
+        let (decoded, consumed) = Psbt::from_bytes(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(decoded.unsigned_tx, tx);
+        assert_eq!(decoded, psbt);
+    }
 }